@@ -1,4 +1,15 @@
+// The interface description only depends on `frc42_dispatch`, also a build
+// dependency.
+#[allow(dead_code)]
+#[path = "src/abi.rs"]
+mod abi;
+
 fn main() {
+    // Write the interface description for `ABI_JSON`.
+    let out_dir = std::env::var("OUT_DIR").expect("OUT_DIR is set by cargo");
+    let path = std::path::Path::new(&out_dir).join("abi.json");
+    std::fs::write(&path, abi::to_json()).expect("failed to write abi.json");
+
     use substrate_wasm_builder::WasmBuilder;
     WasmBuilder::new()
         .with_current_project()
@@ -8,4 +19,4 @@ fn main() {
         .append_to_rust_flags("-Clto=true")
         .append_to_rust_flags("-Copt-level=z")
         .build()
-}
\ No newline at end of file
+}
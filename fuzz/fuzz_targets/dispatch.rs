@@ -0,0 +1,43 @@
+//! Random method numbers and parameters through the dispatch layer. Every
+//! call must return or abort with a user exit code, never panic.
+
+#![no_main]
+
+use arbitrary::Arbitrary;
+use fvm_actor_example::abi::METHODS;
+use fvm_actor_example_fuzz::Actor;
+use fvm_shared::MethodNum;
+use libfuzzer_sys::fuzz_target;
+
+#[derive(Arbitrary, Debug)]
+enum Method {
+    /// One of the exported methods, by its index in `abi::METHODS`.
+    Exported(u8),
+    /// Its FRC-42 number instead of the legacy one.
+    Frc42(u8),
+    Any(MethodNum),
+}
+
+impl Method {
+    fn number(&self) -> MethodNum {
+        match *self {
+            Method::Exported(index) => METHODS[index as usize % METHODS.len()].number(),
+            Method::Frc42(index) => {
+                let method = &METHODS[index as usize % METHODS.len()];
+                method.hashed.unwrap_or_else(|| method.number())
+            }
+            Method::Any(number) => number,
+        }
+    }
+}
+
+thread_local! {
+    static ACTOR: Actor = Actor::new();
+}
+
+fuzz_target!(|input: (Method, Vec<u8>)| {
+    let (method, params) = input;
+    ACTOR.with(|actor| {
+        let _ = actor.call(method.number(), &params);
+    });
+});
@@ -0,0 +1,36 @@
+//! Random bytes as the parameters of every method that takes CBOR
+//! parameters. Bytes that aren't a single well-formed CBOR item can't be the
+//! parameters of any method, so they must be rejected with
+//! `USR_SERIALIZATION`.
+
+#![no_main]
+
+use fvm_actor_example::abi::METHODS;
+use fvm_actor_example_fuzz::Actor;
+use fvm_shared::error::ExitCode;
+use libfuzzer_sys::fuzz_target;
+use serde::de::IgnoredAny;
+
+thread_local! {
+    static ACTOR: Actor = Actor::new();
+}
+
+fuzz_target!(|params: &[u8]| {
+    let well_formed = fvm_ipld_encoding::from_slice::<IgnoredAny>(params).is_ok();
+    ACTOR.with(|actor| {
+        let methods = METHODS
+            .iter()
+            .filter(|method| !matches!(method.params, None | Some("raw")));
+        for method in methods {
+            let outcome = actor.call(method.number(), params);
+            if !well_formed {
+                assert!(
+                    matches!(&outcome, Err(abort) if abort.exit_code == ExitCode::USR_SERIALIZATION),
+                    "{} accepted malformed params: {:?}",
+                    method.name,
+                    outcome
+                );
+            }
+        }
+    });
+});
@@ -0,0 +1,78 @@
+//! Setup shared by the fuzz targets: the actor, constructed on a
+//! `MockRuntime`, with every call starting from the freshly constructed
+//! state.
+
+use std::panic;
+use std::rc::Rc;
+
+use cid::Cid;
+use fvm_actor_example::dispatch;
+use fvm_actor_example::runtime::{Abort, MockRuntime};
+use fvm_ipld_encoding::RawBytes;
+use fvm_shared::econ::TokenAmount;
+use fvm_shared::error::ErrorNumber;
+use fvm_shared::{ActorID, MethodNum};
+
+const INIT_ACTOR: ActorID = 1;
+/// This actor.
+pub const RECEIVER: ActorID = 100;
+/// The account that created the actor, so that calls get past the admin
+/// checks and reach the rest of the method.
+pub const ADMIN: ActorID = 10;
+
+pub struct Actor {
+    pub rt: Rc<MockRuntime>,
+    root: Cid,
+}
+
+impl Actor {
+    /// Constructs the actor on a mock runtime installed for this thread.
+    pub fn new() -> Self {
+        // libfuzzer aborts the process on any panic, but aborts of the actor
+        // are panics carrying an `Abort` that `MockRuntime::call` catches.
+        let report = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            if info.payload().downcast_ref::<Abort>().is_none() {
+                report(info);
+            }
+        }));
+
+        let rt = MockRuntime::install(RECEIVER);
+        rt.caller.set(INIT_ACTOR);
+        rt.origin.set(ADMIN);
+        rt.call(1, dispatch, RawBytes::default())
+            .expect("the constructor succeeds");
+        rt.set_balance(TokenAmount::from_atto(1_000_000_000_000_000_000u128));
+        // There are no other actors, so every send fails.
+        rt.unexpected_sends
+            .replace(Some(Err(ErrorNumber::NotFound)));
+
+        let root = rt.root.get().expect("the constructor set a root");
+        Self { rt, root }
+    }
+
+    /// Calls `method` with `params` as the admin. Aborts must be user errors:
+    /// system exit codes are reserved for the VM.
+    pub fn call(&self, method: MethodNum, params: &[u8]) -> Result<Option<RawBytes>, Abort> {
+        self.rt.root.set(Some(self.root));
+        self.rt.set_caller(ADMIN);
+        let outcome = self
+            .rt
+            .call(method, dispatch, RawBytes::new(params.to_vec()));
+        if let Err(abort) = &outcome {
+            assert!(
+                !abort.exit_code.is_system_error(),
+                "method {} aborted with system exit code {:?}",
+                method,
+                abort
+            );
+        }
+        outcome
+    }
+}
+
+impl Default for Actor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
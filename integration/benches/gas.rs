@@ -0,0 +1,261 @@
+//! Gas used by each method, and how it grows with the state the method
+//! walks: `get_power_actor_miners` with the number of power claims, share
+//! ledger operations with the number of holders.
+//!
+//! Gas is deterministic for a given actor build and builtin-actors bundle,
+//! so the numbers are compared with the committed `gas-baseline.txt`. Any
+//! measurement over its baseline by more than `TOLERANCE_PERCENT` fails the
+//! run, and so does a missing baseline or a measurement it doesn't list, until
+//! recorded with `--bless`.
+//!
+//! ```sh
+//! BUILTIN_ACTORS_BUNDLE=... cargo bench --bench gas              # compare
+//! BUILTIN_ACTORS_BUNDLE=... cargo bench --bench gas -- --bless   # record
+//! ```
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use anyhow::{anyhow, Context, Result};
+use fvm_actor_example::token::BALANCE_OF;
+use fvm_actor_example::{CidParams, CreateMinerParams, StateHistoryParams};
+use fvm_actor_example_integration::{Harness, POWER_ACTOR};
+use fvm_ipld_encoding::RawBytes;
+use fvm_shared::address::Address;
+use fvm_shared::econ::TokenAmount;
+use fvm_shared::sector::RegisteredPoStProof;
+use fvm_shared::MethodNum;
+use serde::Serialize;
+
+const BASELINE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/gas-baseline.txt");
+
+/// How much more gas than the baseline a measurement may use.
+const TOLERANCE_PERCENT: u64 = 1;
+
+/// Power claims `get_power_actor_miners` is measured at.
+const CLAIM_COUNTS: &[usize] = &[0, 1, 10, 50];
+/// Share holders the ledger operations are measured at.
+const HOLDER_COUNTS: &[usize] = &[1, 10, 100, 500];
+/// Parameter sizes `echo_raw_bytes` is measured at.
+const PARAM_SIZES: &[usize] = &[0, 32, 1024, 16 * 1024];
+
+/// Gas used by each measurement, by name.
+type Measurements = BTreeMap<String, u64>;
+
+fn fil(whole: u64) -> TokenAmount {
+    TokenAmount::from_atto(whole as u128 * 1_000_000_000_000_000_000)
+}
+
+/// Calls `method` of the actor from the admin and returns the gas used.
+fn gas<P: Serialize>(h: &mut Harness, method: MethodNum, params: &P) -> u64 {
+    let admin = h.admin();
+    gas_with_value(h, admin, method, params, TokenAmount::from_atto(0))
+}
+
+fn gas_with_value<P: Serialize>(
+    h: &mut Harness,
+    from: Address,
+    method: MethodNum,
+    params: &P,
+    value: TokenAmount,
+) -> u64 {
+    let to = h.actor;
+    let params = RawBytes::serialize(params).unwrap();
+    let receipt = h.apply(from, to, method, params, value).msg_receipt;
+    assert!(
+        receipt.exit_code.is_success(),
+        "method {} failed: {:?}",
+        method,
+        receipt
+    );
+    receipt.gas_used as u64
+}
+
+/// Methods whose cost doesn't depend on the state.
+fn measure_methods(m: &mut Measurements) {
+    let mut h = Harness::new();
+    let admin = h.admin();
+    let actor = h.actor;
+    h.transfer(admin, actor, fil(10));
+
+    m.insert("say_hello".into(), gas(&mut h, 2, &()));
+    m.insert("get_state_cid".into(), gas(&mut h, 3, &()));
+    m.insert("get_state_cid_cbor".into(), gas(&mut h, 5, &()));
+    let (_, root) = h.actor_state(actor.id().unwrap()).unwrap();
+    m.insert(
+        "echo_cid_params".into(),
+        gas(&mut h, 6, &CidParams { cid: root }),
+    );
+    m.insert(
+        "get_old_state".into(),
+        gas(&mut h, 7, &StateHistoryParams { index: 0 }),
+    );
+    m.insert(
+        "get_state_as_bytes".into(),
+        gas(&mut h, 8, &StateHistoryParams { index: 0 }),
+    );
+    let (_, power) = h.actor_state(POWER_ACTOR).unwrap();
+    m.insert(
+        "get_power_actor_state".into(),
+        gas(&mut h, 9, &CidParams { cid: power }),
+    );
+    m.insert("get_current_balance".into(), gas(&mut h, 10, &()));
+    m.insert("withdraw".into(), gas(&mut h, 12, &(fil(1),)));
+    m.insert("fund_t04".into(), gas(&mut h, 14, &(fil(1),)));
+
+    for &size in PARAM_SIZES {
+        let params = RawBytes::new(vec![0xa5; size]);
+        m.insert(
+            format!("echo_raw_bytes/{}_bytes", size),
+            gas(&mut h, 4, &params),
+        );
+    }
+}
+
+/// `get_power_actor_miners` walks every power claim.
+fn measure_miners(m: &mut Measurements) {
+    let mut h = Harness::new();
+    let worker = h.new_account(fil(1));
+    let actor = h.actor;
+
+    let mut created = 0;
+    for &claims in CLAIM_COUNTS {
+        while created < claims {
+            let params = CreateMinerParams {
+                owner: actor,
+                worker,
+                window_post_proof_type: RegisteredPoStProof::StackedDRGWindow32GiBV1P1,
+                peer: b"peer".to_vec(),
+                multiaddrs: vec![],
+            };
+            let used = gas(&mut h, 15, &params);
+            created += 1;
+            if created == claims {
+                m.insert(format!("create_miner_1/{}_claims", claims), used);
+            }
+        }
+        let (_, power) = h.actor_state(POWER_ACTOR).unwrap();
+        m.insert(
+            format!("get_power_actor_miners/{}_claims", claims),
+            gas(&mut h, 11, &CidParams { cid: power }),
+        );
+    }
+}
+
+/// Share ledger operations read and write the holder HAMT.
+fn measure_ledger(m: &mut Measurements) {
+    let mut h = Harness::new();
+
+    let mut holders = Vec::new();
+    for &count in HOLDER_COUNTS {
+        while holders.len() < count {
+            let holder = h.new_account(fil(2));
+            let used = gas_with_value(&mut h, holder, 49, &(), fil(1));
+            holders.push(holder);
+            if holders.len() == count {
+                m.insert(format!("deposit/{}_holders", count), used);
+            }
+        }
+        let first = holders[0];
+        m.insert(
+            format!("balance_of/{}_holders", count),
+            gas(&mut h, BALANCE_OF, &first),
+        );
+    }
+}
+
+fn read_baseline(path: &Path) -> Result<Measurements> {
+    let text = std::fs::read_to_string(path)?;
+    let mut baseline = Measurements::new();
+    for (number, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (name, gas) = line
+            .split_once(char::is_whitespace)
+            .ok_or_else(|| anyhow!("line {}: expected `name gas`", number + 1))?;
+        let gas = gas
+            .trim()
+            .parse()
+            .with_context(|| format!("line {}: invalid gas", number + 1))?;
+        baseline.insert(name.to_string(), gas);
+    }
+    Ok(baseline)
+}
+
+fn write_baseline(path: &Path, m: &Measurements) -> Result<()> {
+    let mut text = String::from(
+        "# Gas used per method, see benches/gas.rs. Regenerate with\n\
+         # `cargo bench --bench gas -- --bless`.\n",
+    );
+    for (name, gas) in m {
+        text.push_str(&format!("{} {}\n", name, gas));
+    }
+    std::fs::write(path, text)?;
+    Ok(())
+}
+
+fn main() -> Result<()> {
+    let bless = std::env::args().any(|arg| arg == "--bless");
+
+    let mut m = Measurements::new();
+    measure_methods(&mut m);
+    measure_miners(&mut m);
+    measure_ledger(&mut m);
+
+    let path = Path::new(BASELINE);
+    if bless {
+        write_baseline(path, &m)?;
+        println!("wrote {} measurements to {}", m.len(), path.display());
+        return Ok(());
+    }
+
+    let baseline = read_baseline(path)
+        .with_context(|| format!("reading {}, record it with `-- --bless`", path.display()))?;
+    let mut regressions = 0;
+    let mut unrecorded = 0;
+    println!(
+        "{:<40} {:>14} {:>14} {:>9}",
+        "", "gas", "baseline", "change"
+    );
+    for (name, &gas) in &m {
+        match baseline.get(name) {
+            Some(&base) => {
+                let change = (gas as f64 - base as f64) / base as f64 * 100.0;
+                let regressed = gas * 100 > base * (100 + TOLERANCE_PERCENT);
+                println!(
+                    "{:<40} {:>14} {:>14} {:>+8.2}%{}",
+                    name,
+                    gas,
+                    base,
+                    change,
+                    if regressed { "  REGRESSION" } else { "" }
+                );
+                regressions += regressed as usize;
+            }
+            None => {
+                println!("{:<40} {:>14} {:>14}  NOT RECORDED", name, gas, "-");
+                unrecorded += 1;
+            }
+        }
+    }
+    for name in baseline.keys().filter(|name| !m.contains_key(*name)) {
+        println!("{:<40} {:>14} (no longer measured)", name, "-");
+    }
+
+    if unrecorded > 0 {
+        return Err(anyhow!(
+            "{} measurements are not in the baseline, record them with `-- --bless`",
+            unrecorded
+        ));
+    }
+    if regressions > 0 {
+        return Err(anyhow!(
+            "{} measurements use more than {}% more gas than the baseline",
+            regressions,
+            TOLERANCE_PERCENT
+        ));
+    }
+    Ok(())
+}
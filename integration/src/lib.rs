@@ -0,0 +1,318 @@
+//! Runs the compiled actor in a local FVM with the builtin actors, the same
+//! way it runs on a devnet: the code is installed and the actor created
+//! through the init actor (`lotus chain install-actor` / `create-actor`),
+//! then methods are invoked with messages from accounts.
+//!
+//! The builtin actors come from the bundle at `$BUILTIN_ACTORS_BUNDLE`, the
+//! devnet bundle built from the builtin-actors branch in the README.
+
+use std::collections::HashMap;
+
+use cid::Cid;
+use fvm::executor::{ApplyKind, ApplyRet, Executor};
+use fvm::machine::Machine;
+use fvm::state_tree::StateTree;
+use fvm_integration_tests::bundle;
+use fvm_integration_tests::dummy::DummyExterns;
+use fvm_integration_tests::tester::{Account, Tester};
+use fvm_ipld_blockstore::MemoryBlockstore;
+use fvm_ipld_encoding::tuple::{Deserialize_tuple, Serialize_tuple};
+use fvm_ipld_encoding::{to_vec, CborStore, RawBytes};
+use fvm_shared::address::{Address, BLS_PUB_LEN};
+use fvm_shared::clock::ChainEpoch;
+use fvm_shared::econ::TokenAmount;
+use fvm_shared::message::Message;
+use fvm_shared::receipt::Receipt;
+use fvm_shared::state::StateTreeVersion;
+use fvm_shared::version::NetworkVersion;
+use fvm_shared::{ActorID, MethodNum, METHOD_SEND};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+pub const INIT_ACTOR: ActorID = 1;
+pub const POWER_ACTOR: ActorID = 4;
+
+/// Init actor methods.
+pub const INIT_EXEC: MethodNum = 2;
+pub const INIT_INSTALL_CODE: MethodNum = 4;
+
+/// Gas limit of every message, the block gas limit.
+const GAS_LIMIT: i64 = 10_000_000_000;
+
+#[derive(Serialize_tuple, Deserialize_tuple)]
+pub struct InstallParams {
+    pub code: RawBytes,
+}
+
+#[derive(Serialize_tuple, Deserialize_tuple)]
+pub struct InstallReturn {
+    pub code_cid: Cid,
+    pub installed: bool,
+}
+
+#[derive(Serialize_tuple, Deserialize_tuple)]
+pub struct ExecParams {
+    pub code_cid: Cid,
+    pub constructor_params: RawBytes,
+}
+
+#[derive(Serialize_tuple, Deserialize_tuple)]
+pub struct ExecReturn {
+    pub id_address: Address,
+    pub robust_address: Address,
+}
+
+pub struct Harness {
+    pub tester: Tester<MemoryBlockstore, DummyExterns>,
+    /// Funded accounts, the first of which created the actor and is its
+    /// admin.
+    pub accounts: [Account; 3],
+    /// ID address of the actor.
+    pub actor: Address,
+    /// Epoch messages are applied at, 0 until `advance_epoch`.
+    pub epoch: ChainEpoch,
+    sequences: HashMap<Address, u64>,
+    /// Accounts created by `new_account` so far.
+    created_accounts: u64,
+}
+
+impl Harness {
+    /// Sets up the builtin actors and funded accounts, and creates the actor.
+    pub fn new() -> Self {
+        let path = std::env::var("BUILTIN_ACTORS_BUNDLE")
+            .expect("BUILTIN_ACTORS_BUNDLE must point to a builtin-actors bundle (.car)");
+        let car = std::fs::read(&path)
+            .unwrap_or_else(|err| panic!("failed to read bundle {}: {}", path, err));
+        let blockstore = MemoryBlockstore::default();
+        let bundle = bundle::import_bundle(&blockstore, &car).unwrap();
+
+        let mut tester = Tester::new(
+            NetworkVersion::V18,
+            StateTreeVersion::V5,
+            bundle,
+            blockstore,
+        )
+        .unwrap();
+        let accounts = tester.create_accounts().unwrap();
+        tester.instantiate_machine(DummyExterns).unwrap();
+
+        let mut harness = Harness {
+            tester,
+            accounts,
+            actor: Address::new_id(0),
+            epoch: 0,
+            sequences: HashMap::new(),
+            created_accounts: 0,
+        };
+        let admin = harness.admin();
+        let wasm = fvm_actor_example::WASM_BINARY.expect("the actor was built without its WASM");
+        let installed: InstallReturn = harness.send_ok(
+            admin,
+            Address::new_id(INIT_ACTOR),
+            INIT_INSTALL_CODE,
+            &InstallParams {
+                code: RawBytes::new(wasm.to_vec()),
+            },
+        );
+        let created: ExecReturn = harness.send_ok(
+            admin,
+            Address::new_id(INIT_ACTOR),
+            INIT_EXEC,
+            &ExecParams {
+                code_cid: installed.code_cid,
+                constructor_params: RawBytes::default(),
+            },
+        );
+        harness.actor = created.id_address;
+        harness
+    }
+
+    pub fn admin(&self) -> Address {
+        self.accounts[0].1
+    }
+
+    /// Creates a BLS account holding `balance`, by sending it from the admin
+    /// to a fresh key address, and returns its ID address. BLS accounts can
+    /// also be miner workers.
+    pub fn new_account(&mut self, balance: TokenAmount) -> Address {
+        self.created_accounts += 1;
+        let mut key = [0xaa; BLS_PUB_LEN];
+        key[..8].copy_from_slice(&self.created_accounts.to_be_bytes());
+        let addr = Address::new_bls(&key).unwrap();
+
+        let admin = self.admin();
+        self.transfer(admin, addr, balance);
+        let id = self.resolve(&addr).expect("the account was created");
+        Address::new_id(id)
+    }
+
+    /// Moves the chain `epochs` forward, by flushing the state tree and
+    /// restarting the machine on it at the later epoch.
+    pub fn advance_epoch(&mut self, epochs: ChainEpoch) {
+        let mut executor = self
+            .tester
+            .executor
+            .take()
+            .expect("the machine is instantiated");
+        let root = executor.flush().unwrap();
+        let blockstore = executor
+            .into_machine()
+            .expect("the executor has a machine")
+            .into_store()
+            .into_inner();
+        self.tester.state_tree = Some(StateTree::new_from_root(blockstore, &root).unwrap());
+
+        self.epoch += epochs;
+        let epoch = self.epoch;
+        self.tester
+            .instantiate_machine_with_config(DummyExterns, |_| (), |mc| mc.epoch = epoch)
+            .unwrap();
+    }
+
+    /// Applies a message from `from`, which must be an account.
+    pub fn apply(
+        &mut self,
+        from: Address,
+        to: Address,
+        method: MethodNum,
+        params: RawBytes,
+        value: TokenAmount,
+    ) -> ApplyRet {
+        let sequence = self.sequences.entry(from).or_default();
+        let message = Message {
+            version: 0,
+            from,
+            to,
+            sequence: *sequence,
+            value,
+            method_num: method,
+            params,
+            gas_limit: GAS_LIMIT,
+            gas_fee_cap: TokenAmount::from_atto(1),
+            gas_premium: TokenAmount::from_atto(1),
+        };
+        *sequence += 1;
+
+        let raw_length = to_vec(&message).unwrap().len();
+        self.tester
+            .executor
+            .as_mut()
+            .expect("the machine is instantiated")
+            .execute_message(message, ApplyKind::Explicit, raw_length)
+            .unwrap()
+    }
+
+    /// Calls `method` of the actor from `from` and returns the receipt.
+    pub fn call<P: Serialize>(&mut self, from: Address, method: MethodNum, params: &P) -> Receipt {
+        let to = self.actor;
+        self.send(from, to, method, params)
+    }
+
+    /// Calls `method` of the actor from `from`, asserting that it succeeds,
+    /// and decodes its return value.
+    pub fn call_ok<P: Serialize, R: DeserializeOwned>(
+        &mut self,
+        from: Address,
+        method: MethodNum,
+        params: &P,
+    ) -> R {
+        let to = self.actor;
+        self.send_ok(from, to, method, params)
+    }
+
+    pub fn send<P: Serialize>(
+        &mut self,
+        from: Address,
+        to: Address,
+        method: MethodNum,
+        params: &P,
+    ) -> Receipt {
+        let params = RawBytes::serialize(params).unwrap();
+        self.apply(from, to, method, params, TokenAmount::from_atto(0))
+            .msg_receipt
+    }
+
+    pub fn send_ok<P: Serialize, R: DeserializeOwned>(
+        &mut self,
+        from: Address,
+        to: Address,
+        method: MethodNum,
+        params: &P,
+    ) -> R {
+        let receipt = self.send(from, to, method, params);
+        assert!(
+            receipt.exit_code.is_success(),
+            "method {} of {} failed: {:?}",
+            method,
+            to,
+            receipt
+        );
+        receipt.return_data.deserialize().unwrap()
+    }
+
+    /// Sends `value` from the account `from` to `to`.
+    pub fn transfer(&mut self, from: Address, to: Address, value: TokenAmount) {
+        let ret = self.apply(from, to, METHOD_SEND, RawBytes::default(), value);
+        assert!(
+            ret.msg_receipt.exit_code.is_success(),
+            "{:?}",
+            ret.msg_receipt
+        );
+    }
+
+    fn machine(&self) -> &impl Machine {
+        &**self
+            .tester
+            .executor
+            .as_ref()
+            .expect("the machine is instantiated")
+    }
+
+    /// Balance and state root of the actor `id`, if it exists.
+    pub fn actor_state(&self, id: ActorID) -> Option<(TokenAmount, Cid)> {
+        self.machine()
+            .state_tree()
+            .get_actor_id(id)
+            .unwrap()
+            .map(|actor| (actor.balance, actor.state))
+    }
+
+    /// ID of the actor at `addr`, if it exists.
+    pub fn resolve(&self, addr: &Address) -> Option<ActorID> {
+        self.machine().state_tree().lookup_id(addr).unwrap()
+    }
+
+    pub fn balance(&self, addr: &Address) -> TokenAmount {
+        let id = addr.id().expect("an ID address");
+        self.actor_state(id)
+            .map(|(balance, _)| balance)
+            .unwrap_or_default()
+    }
+
+    /// The root object of the actor, pointing at its state and history.
+    pub fn state_root(&self) -> fvm_actor_example::StateRoot {
+        let (_, root) = self
+            .actor_state(self.actor.id().unwrap())
+            .expect("the actor exists");
+        self.machine()
+            .blockstore()
+            .get_cbor(&root)
+            .unwrap()
+            .expect("the state root block exists")
+    }
+
+    /// The current state of the actor.
+    pub fn state(&self) -> fvm_actor_example::State {
+        self.machine()
+            .blockstore()
+            .get_cbor(&self.state_root().state)
+            .unwrap()
+            .expect("the state block exists")
+    }
+}
+
+impl Default for Harness {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,270 @@
+//! Methods 1–19, running queued timelock actions and the miner funding
+//! methods, called with messages from accounts, against the real init, power
+//! and miner actors.
+
+use cid::Cid;
+use fvm_actor_example::miner::{MinerFunds, MinerFundsParams, TopUpPledgeParams};
+use fvm_actor_example::multisig::ProposeReturn;
+use fvm_actor_example::timelock::{QueueReturn, QueuedIDParams, DEFAULT_TIMELOCK_DELAY};
+use fvm_actor_example::{
+    CidParams, CreateMinerParams, CreateMinerParamsReq, CreateMinerReturn, PowerActorState,
+    StateHistoryParams, StateSnapshot,
+};
+use fvm_actor_example_integration::{Harness, POWER_ACTOR};
+use fvm_ipld_encoding::RawBytes;
+use fvm_shared::address::Address;
+use fvm_shared::econ::TokenAmount;
+use fvm_shared::error::ExitCode;
+use fvm_shared::sector::RegisteredPoStProof;
+
+/// Miner method changing (and, called by the new owner, confirming) the owner.
+const MINER_CHANGE_OWNER_ADDRESS: u64 = 23;
+
+fn fil(whole: u64) -> TokenAmount {
+    TokenAmount::from_atto(whole as u128 * 1_000_000_000_000_000_000)
+}
+
+fn create_miner_params(owner: Address, worker: Address) -> CreateMinerParams {
+    CreateMinerParams {
+        owner,
+        worker,
+        window_post_proof_type: RegisteredPoStProof::StackedDRGWindow32GiBV1P1,
+        peer: b"peer".to_vec(),
+        multiaddrs: vec![],
+    }
+}
+
+/// Creates a miner through method 15.
+fn create_miner(h: &mut Harness, owner: Address, worker: Address) -> Address {
+    let admin = h.admin();
+    let ret: CreateMinerReturn = h.call_ok(admin, 15, &create_miner_params(owner, worker));
+    ret.id_address
+}
+
+fn power_state_cid(h: &Harness) -> Cid {
+    h.actor_state(POWER_ACTOR)
+        .expect("the power actor exists")
+        .1
+}
+
+#[test]
+fn constructor_is_run_by_init_only() {
+    let mut h = Harness::new();
+    let state = h.state();
+    assert_eq!(Address::new_id(state.admin), h.admin());
+
+    // Calling it again directly is not allowed.
+    let admin = h.admin();
+    let receipt = h.call(admin, 1, &());
+    assert_eq!(receipt.exit_code, ExitCode::USR_FORBIDDEN);
+}
+
+#[test]
+fn say_hello_and_state_history() {
+    let mut h = Harness::new();
+    let admin = h.admin();
+    let actor = h.actor.id().unwrap();
+
+    let before = h.state_root().state;
+    let hello: String = h.call_ok(admin, 2, &());
+    assert_eq!(
+        hello,
+        format!(
+            "Hello world {}/{}/{} #1!",
+            admin.id().unwrap(),
+            admin.id().unwrap(),
+            actor
+        )
+    );
+    assert_eq!(h.state().count, 1);
+
+    let root = h.actor_state(actor).unwrap().1;
+    let receipt = h.call(admin, 3, &());
+    assert!(receipt.exit_code.is_success());
+    assert_eq!(Cid::try_from(receipt.return_data.bytes()).unwrap(), root);
+    let ret: CidParams = h.call_ok(admin, 5, &());
+    assert_eq!(ret.cid, root);
+
+    let snapshot: StateSnapshot = h.call_ok(admin, 7, &StateHistoryParams { index: 0 });
+    assert_eq!(snapshot.root, before);
+    assert_eq!(snapshot.state.count, 0);
+    let receipt = h.call(admin, 8, &StateHistoryParams { index: 0 });
+    assert!(receipt.exit_code.is_success());
+    assert!(!receipt.return_data.bytes().is_empty());
+    let receipt = h.call(admin, 8, &StateHistoryParams { index: 5 });
+    assert_eq!(receipt.exit_code, ExitCode::USR_NOT_FOUND);
+}
+
+#[test]
+fn echo_methods_return_their_params() {
+    let mut h = Harness::new();
+    let admin = h.admin();
+
+    let echoed: String = h.call_ok(admin, 4, &"hi");
+    assert_eq!(
+        echoed,
+        format!("Params {:?}", RawBytes::serialize("hi").unwrap())
+    );
+
+    let params = CidParams {
+        cid: power_state_cid(&h),
+    };
+    let echoed: String = h.call_ok(admin, 6, &params);
+    assert_eq!(echoed, format!("Params {:?}", params));
+}
+
+#[test]
+fn balance_funding_and_withdrawal() {
+    let mut h = Harness::new();
+    let admin = h.admin();
+    let actor = h.actor;
+    h.transfer(admin, actor, fil(10));
+
+    let balance: String = h.call_ok(admin, 10, &());
+    assert_eq!(balance, fil(10).to_string());
+
+    let power_before = h.balance(&Address::new_id(POWER_ACTOR));
+    let _: String = h.call_ok(admin, 14, &(fil(1),));
+    assert_eq!(
+        h.balance(&Address::new_id(POWER_ACTOR)),
+        power_before.clone() + fil(1)
+    );
+    // Only the admin may fund the power actor from the actor's balance.
+    let other = h.accounts[1].1;
+    let receipt = h.call(other, 14, &(fil(1),));
+    assert_eq!(receipt.exit_code, ExitCode::USR_FORBIDDEN);
+    assert_eq!(
+        h.balance(&Address::new_id(POWER_ACTOR)),
+        power_before + fil(1)
+    );
+
+    let ret: ProposeReturn = h.call_ok(admin, 12, &(fil(2),));
+    assert!(ret.applied);
+    assert_eq!(h.balance(&actor), fil(7));
+}
+
+#[test]
+fn miners_are_created_through_the_power_actor() {
+    let mut h = Harness::new();
+    let worker = h.new_account(fil(1));
+    let actor = h.actor;
+
+    let miner = create_miner(&mut h, actor, worker);
+    assert!(h.state().is_managed_miner(&miner));
+
+    let params = CidParams {
+        cid: power_state_cid(&h),
+    };
+    let power: PowerActorState = h.call_ok(h.admin(), 9, &params);
+    assert_eq!(power.miner_count, 1);
+    let miners: Vec<Address> = h.call_ok(h.admin(), 11, &params);
+    assert_eq!(miners, vec![miner]);
+
+    // The miner actor only accepts account workers, not this actor.
+    let admin = h.admin();
+    let req = CreateMinerParamsReq {
+        window_post_proof_type: RegisteredPoStProof::StackedDRGWindow32GiBV1P1,
+        peer: b"peer".to_vec(),
+    };
+    let receipt = h.call(admin, 13, &req);
+    assert!(!receipt.exit_code.is_success());
+}
+
+#[test]
+fn miners_are_taken_over_and_managed() {
+    let mut h = Harness::new();
+    let worker = h.new_account(fil(1));
+    let owner = h.accounts[1].1;
+    let miner = create_miner(&mut h, owner, worker);
+    assert!(!h.state().is_managed_miner(&miner));
+
+    // The owner proposes this actor, which confirms.
+    let actor = h.actor;
+    let ret = h.send(owner, miner, MINER_CHANGE_OWNER_ADDRESS, &actor);
+    assert!(ret.exit_code.is_success(), "{:?}", ret);
+    let ret: ProposeReturn = h.call_ok(h.admin(), 16, &miner);
+    assert!(ret.applied);
+    assert!(h.state().is_managed_miner(&miner));
+
+    // Funds withdrawn from the miner go to this actor, its owner.
+    let admin = h.admin();
+    h.transfer(admin, miner, fil(2));
+    let miner_before = h.balance(&miner);
+    let actor_before = h.balance(&actor);
+    let ret: ProposeReturn = h.call_ok(admin, 19, &(miner, fil(1)));
+    assert!(ret.applied);
+    assert_eq!(
+        ret.ret.deserialize::<String>().unwrap(),
+        format!("Withdraw request {} => withdrawn {}", fil(1), fil(1))
+    );
+    assert_eq!(h.balance(&miner), miner_before - fil(1));
+    assert_eq!(h.balance(&actor), actor_before + fil(1));
+
+    // Worker changes wait for the timelock.
+    let new_worker = h.new_account(fil(1));
+    let ret: ProposeReturn = h.call_ok(admin, 18, &(miner, new_worker));
+    let queued: QueueReturn = ret.ret.deserialize().unwrap();
+    assert_eq!(queued.eta, h.epoch + DEFAULT_TIMELOCK_DELAY);
+    let params = QueuedIDParams { id: queued.id };
+    let receipt = h.call(admin, 29, &params);
+    assert_eq!(receipt.exit_code, ExitCode::USR_FORBIDDEN);
+
+    // Anyone may run it once the delay has passed, and only once.
+    h.advance_epoch(DEFAULT_TIMELOCK_DELAY);
+    let other = h.accounts[1].1;
+    let ret: String = h.call_ok(other, 29, &params);
+    assert_eq!(ret, format!("ChangeWorker {:?} -> {:?}", miner, new_worker));
+    let receipt = h.call(admin, 30, &params);
+    assert_eq!(receipt.exit_code, ExitCode::USR_NOT_FOUND);
+}
+
+#[test]
+fn miner_funds_are_read_from_the_miner_state() {
+    let mut h = Harness::new();
+    let worker = h.new_account(fil(1));
+    let admin = h.admin();
+    let actor = h.actor;
+    h.transfer(admin, actor, fil(5));
+    let miner = create_miner(&mut h, actor, worker);
+
+    // The state of a freshly created miner decodes, with nothing locked.
+    let miner_state = h.actor_state(miner.id().unwrap()).unwrap().1;
+    let params = MinerFundsParams {
+        miner_states: vec![miner_state],
+    };
+    let funds: Vec<MinerFunds> = h.call_ok(admin, 38, &params);
+    assert_eq!(funds.len(), 1);
+    assert_eq!(funds[0].miner, miner);
+    assert_eq!(funds[0].fee_debt, TokenAmount::from_atto(0));
+    assert_eq!(funds[0].locked_funds, TokenAmount::from_atto(0));
+    assert_eq!(funds[0].initial_pledge, TokenAmount::from_atto(0));
+    assert_eq!(funds[0].pre_commit_deposits, TokenAmount::from_atto(0));
+
+    // Without fee debt, the whole amount is sent for pledge.
+    let miner_before = h.balance(&miner);
+    let params = TopUpPledgeParams {
+        miner,
+        miner_state,
+        amount: fil(2),
+    };
+    let receipt = h.call(admin, 37, &params);
+    assert!(receipt.exit_code.is_success(), "{:?}", receipt);
+    assert_eq!(h.balance(&miner), miner_before + fil(2));
+    assert_eq!(h.balance(&actor), fil(3));
+}
+
+#[test]
+fn destruct_pays_out_the_balance() {
+    let mut h = Harness::new();
+    let admin = h.admin();
+    let actor = h.actor;
+    h.transfer(admin, actor, fil(3));
+
+    let ret: ProposeReturn = h.call_ok(admin, 17, &());
+    assert!(ret.applied);
+    assert!(h.actor_state(actor.id().unwrap()).is_none());
+
+    // Deleted actors accept no more calls.
+    let receipt = h.call(admin, 2, &());
+    assert!(!receipt.exit_code.is_success());
+}
@@ -0,0 +1,1661 @@
+//! Machine-readable description of the actor interface. `build.rs` includes
+//! this module and writes `to_json()` to `$OUT_DIR/abi.json`, which the crate
+//! exposes as `ABI_JSON`. This module only depends on `frc42_dispatch`, so
+//! that the build script can include it.
+//!
+//! Types are named by their path in the crate. Parameters and return values
+//! are DAG-CBOR, encoded as follows:
+//!
+//! - `Tuple` structs are arrays of their fields, in order.
+//! - `Transparent` structs are encoded as their only field.
+//! - `Enum` types are arrays of the variant index followed by the variant's
+//!   fields, e.g. `[1, to, amount]`; the index is the variant's position in
+//!   the list of fields, starting at 0.
+//! - `Option<T>` is `T` or null, `Vec<T>` an array of `T`, and a type in
+//!   parentheses an array of the listed types.
+//! - `Vec<u8>` fields are byte strings; `Address`, `Cid`, `TokenAmount`,
+//!   `RawBytes` and `BitField` use their fvm_shared encodings.
+//! - `raw` values are not CBOR, but the bytes themselves.
+
+use frc42_dispatch::method_hash;
+
+/// Same as `fvm_shared::MethodNum`, which the build script doesn't depend on.
+pub type MethodNum = u64;
+
+/// How a type is laid out in CBOR.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Encoding {
+    Tuple,
+    Transparent,
+    Enum,
+}
+
+/// A struct field, or an enum variant with the types of its fields.
+#[derive(Debug)]
+pub struct Field {
+    pub name: &'static str,
+    pub ty: &'static str,
+}
+
+#[derive(Debug)]
+pub struct TypeDef {
+    pub name: &'static str,
+    pub encoding: Encoding,
+    pub fields: &'static [Field],
+}
+
+/// An exported method. Methods without a legacy number are only reachable by
+/// their FRC-0042 number.
+#[derive(Debug)]
+pub struct Method {
+    pub legacy: Option<MethodNum>,
+    pub name: &'static str,
+    /// The FRC-0042 number of `name`, if the method is reachable by it. The
+    /// constructor is only reachable by its legacy number.
+    pub hashed: Option<MethodNum>,
+    pub params: Option<&'static str>,
+    pub ret: Option<&'static str>,
+}
+
+impl Method {
+    /// The number the method is dispatched by: its legacy number if any,
+    /// otherwise its FRC-0042 number.
+    pub fn number(&self) -> MethodNum {
+        match self.legacy.or(self.hashed) {
+            Some(number) => number,
+            None => panic!("method {} has no number", self.name),
+        }
+    }
+
+    /// Notes for callers from `METHOD_DOCS`, if the method has any.
+    pub fn doc(&self) -> Option<&'static str> {
+        METHOD_DOCS
+            .iter()
+            .find(|(name, _)| *name == self.name)
+            .map(|(_, doc)| *doc)
+    }
+}
+
+/// Notes for callers, by method name, on what a method trusts the caller
+/// for. `to_json` exports them as the `doc` of the method.
+pub const METHOD_DOCS: &[(&str, &str)] = &[
+    (
+        "TopUpPledge",
+        "The fee debt is read from the caller's miner_state, which is not checked \
+         against the miner's state root. Funds sent beyond the debt stay in the \
+         managed miner.",
+    ),
+    (
+        "GetMinerFunds",
+        "The funds are read from the caller's miner_states, which are not checked \
+         against the miners' state roots. The result is only as accurate as those \
+         states.",
+    ),
+];
+
+/// Types that are not described here, since they come from the FVM crates or
+/// are primitives.
+pub const EXTERNAL_TYPES: &[&str] = &[
+    "raw",
+    "bool",
+    "u8",
+    "u32",
+    "u64",
+    "i64",
+    "String",
+    "ActorID",
+    "Address",
+    "BitField",
+    "BytesDe",
+    "ChainEpoch",
+    "Cid",
+    "FilterEstimate",
+    "MethodNum",
+    "PaddedPieceSize",
+    "RawBytes",
+    "Receipt",
+    "RegisteredPoStProof",
+    "StoragePower",
+    "TokenAmount",
+];
+
+/// The legacy number of a method in `methods.rs`, `_` if it has none.
+macro_rules! legacy_number {
+    (_) => {
+        None
+    };
+    ($legacy:literal) => {
+        Some($legacy)
+    };
+}
+
+/// The FRC-0042 number of a method in `methods.rs`, unless it is
+/// `legacy_only`.
+macro_rules! hashed_number {
+    (legacy_only $name:literal) => {
+        None
+    };
+    ($name:literal) => {
+        Some(method_hash!($name))
+    };
+}
+
+/// The name of a parameter or return type in `methods.rs`.
+macro_rules! type_name {
+    () => {
+        None
+    };
+    (raw) => {
+        Some("raw")
+    };
+    ($ty:ty) => {
+        Some(stringify!($ty))
+    };
+}
+
+/// Generates `METHODS` from the method table. The handlers are only used by
+/// `dispatch`.
+macro_rules! methods {
+    ($(
+        $legacy:tt $($only:ident)? $name:literal
+            => $($handler:ident)::+ ($($params:tt)*) -> ($($ret:tt)*);
+    )*) => {
+        /// The exported methods, in the order of `methods.rs`.
+        pub const METHODS: &[Method] = &[$(
+            Method {
+                legacy: legacy_number!($legacy),
+                name: $name,
+                hashed: hashed_number!($($only)? $name),
+                params: type_name!($($params)*),
+                ret: type_name!($($ret)*),
+            },
+        )*];
+    };
+}
+
+include!("methods.rs");
+
+pub const TYPES: &[TypeDef] = &[
+    TypeDef {
+        name: "CidParams",
+        encoding: Encoding::Tuple,
+        fields: &[Field {
+            name: "cid",
+            ty: "Cid",
+        }],
+    },
+    TypeDef {
+        name: "StateHistoryParams",
+        encoding: Encoding::Tuple,
+        fields: &[Field {
+            name: "index",
+            ty: "u64",
+        }],
+    },
+    TypeDef {
+        name: "StateSnapshot",
+        encoding: Encoding::Tuple,
+        fields: &[
+            Field {
+                name: "root",
+                ty: "Cid",
+            },
+            Field {
+                name: "epoch",
+                ty: "ChainEpoch",
+            },
+            Field {
+                name: "state",
+                ty: "State",
+            },
+        ],
+    },
+    TypeDef {
+        name: "State",
+        encoding: Encoding::Tuple,
+        fields: &[
+            Field {
+                name: "count",
+                ty: "u64",
+            },
+            Field {
+                name: "events",
+                ty: "Cid",
+            },
+            Field {
+                name: "admin",
+                ty: "ActorID",
+            },
+            Field {
+                name: "miners",
+                ty: "Vec<Address>",
+            },
+            Field {
+                name: "miner_methods",
+                ty: "Vec<MethodNum>",
+            },
+            Field {
+                name: "signers",
+                ty: "Vec<ActorID>",
+            },
+            Field {
+                name: "threshold",
+                ty: "u64",
+            },
+            Field {
+                name: "next_tx_id",
+                ty: "u64",
+            },
+            Field {
+                name: "pending",
+                ty: "Cid",
+            },
+            Field {
+                name: "timelock_delay",
+                ty: "ChainEpoch",
+            },
+            Field {
+                name: "next_queued_id",
+                ty: "u64",
+            },
+            Field {
+                name: "queued",
+                ty: "Cid",
+            },
+            Field {
+                name: "beneficiary",
+                ty: "Option<Address>",
+            },
+            Field {
+                name: "global_limit",
+                ty: "Option<ratelimit::RateLimit>",
+            },
+            Field {
+                name: "caller_limit",
+                ty: "Option<ratelimit::RateLimit>",
+            },
+            Field {
+                name: "global_bucket",
+                ty: "ratelimit::Bucket",
+            },
+            Field {
+                name: "caller_buckets",
+                ty: "Cid",
+            },
+            Field {
+                name: "guardian",
+                ty: "ActorID",
+            },
+            Field {
+                name: "paused",
+                ty: "bool",
+            },
+            Field {
+                name: "locked",
+                ty: "bool",
+            },
+            Field {
+                name: "transfer_codes",
+                ty: "Vec<Cid>",
+            },
+            Field {
+                name: "transfer_limit",
+                ty: "TokenAmount",
+            },
+            Field {
+                name: "datacap_balances",
+                ty: "Cid",
+            },
+            Field {
+                name: "share_supply",
+                ty: "TokenAmount",
+            },
+            Field {
+                name: "share_balances",
+                ty: "Cid",
+            },
+            Field {
+                name: "share_allowances",
+                ty: "Cid",
+            },
+        ],
+    },
+    TypeDef {
+        name: "StateRoot",
+        encoding: Encoding::Tuple,
+        fields: &[
+            Field {
+                name: "state",
+                ty: "Cid",
+            },
+            Field {
+                name: "history",
+                ty: "Cid",
+            },
+            Field {
+                name: "pushed",
+                ty: "u64",
+            },
+        ],
+    },
+    TypeDef {
+        name: "StateHistoryEntry",
+        encoding: Encoding::Tuple,
+        fields: &[
+            Field {
+                name: "root",
+                ty: "Cid",
+            },
+            Field {
+                name: "epoch",
+                ty: "ChainEpoch",
+            },
+        ],
+    },
+    TypeDef {
+        name: "PowerActorState",
+        encoding: Encoding::Tuple,
+        fields: &[
+            Field {
+                name: "total_raw_byte_power",
+                ty: "StoragePower",
+            },
+            Field {
+                name: "total_bytes_committed",
+                ty: "StoragePower",
+            },
+            Field {
+                name: "total_quality_adj_power",
+                ty: "StoragePower",
+            },
+            Field {
+                name: "total_qa_bytes_committed",
+                ty: "StoragePower",
+            },
+            Field {
+                name: "total_pledge_collateral",
+                ty: "TokenAmount",
+            },
+            Field {
+                name: "this_epoch_raw_byte_power",
+                ty: "StoragePower",
+            },
+            Field {
+                name: "this_epoch_quality_adj_power",
+                ty: "StoragePower",
+            },
+            Field {
+                name: "this_epoch_pledge_collateral",
+                ty: "TokenAmount",
+            },
+            Field {
+                name: "this_epoch_qa_power_smoothed",
+                ty: "FilterEstimate",
+            },
+            Field {
+                name: "miner_count",
+                ty: "i64",
+            },
+            Field {
+                name: "miner_above_min_power_count",
+                ty: "i64",
+            },
+            Field {
+                name: "cron_event_queue",
+                ty: "Cid",
+            },
+            Field {
+                name: "first_cron_epoch",
+                ty: "ChainEpoch",
+            },
+            Field {
+                name: "claims",
+                ty: "Cid",
+            },
+            Field {
+                name: "proof_validation_batch",
+                ty: "Option<Cid>",
+            },
+        ],
+    },
+    TypeDef {
+        name: "WithdrawalParams",
+        encoding: Encoding::Tuple,
+        fields: &[Field {
+            name: "amount",
+            ty: "TokenAmount",
+        }],
+    },
+    TypeDef {
+        name: "CreateMinerParamsReq",
+        encoding: Encoding::Tuple,
+        fields: &[
+            Field {
+                name: "window_post_proof_type",
+                ty: "RegisteredPoStProof",
+            },
+            Field {
+                name: "peer",
+                ty: "Vec<u8>",
+            },
+        ],
+    },
+    TypeDef {
+        name: "CreateMinerParams",
+        encoding: Encoding::Tuple,
+        fields: &[
+            Field {
+                name: "owner",
+                ty: "Address",
+            },
+            Field {
+                name: "worker",
+                ty: "Address",
+            },
+            Field {
+                name: "window_post_proof_type",
+                ty: "RegisteredPoStProof",
+            },
+            Field {
+                name: "peer",
+                ty: "Vec<u8>",
+            },
+            Field {
+                name: "multiaddrs",
+                ty: "Vec<BytesDe>",
+            },
+        ],
+    },
+    TypeDef {
+        name: "CreateMinerReturn",
+        encoding: Encoding::Tuple,
+        fields: &[
+            Field {
+                name: "id_address",
+                ty: "Address",
+            },
+            Field {
+                name: "robust_address",
+                ty: "Address",
+            },
+            Field {
+                name: "out",
+                ty: "CreateMinerParams",
+            },
+        ],
+    },
+    TypeDef {
+        name: "ChangeWorkerParamsReq",
+        encoding: Encoding::Tuple,
+        fields: &[
+            Field {
+                name: "miner_id",
+                ty: "Address",
+            },
+            Field {
+                name: "new_worker_id",
+                ty: "Address",
+            },
+        ],
+    },
+    TypeDef {
+        name: "WithdrawMinerParams",
+        encoding: Encoding::Tuple,
+        fields: &[
+            Field {
+                name: "miner_id",
+                ty: "Address",
+            },
+            Field {
+                name: "amount",
+                ty: "TokenAmount",
+            },
+        ],
+    },
+    TypeDef {
+        name: "MinerCallParams",
+        encoding: Encoding::Tuple,
+        fields: &[
+            Field {
+                name: "miner",
+                ty: "Address",
+            },
+            Field {
+                name: "method",
+                ty: "MethodNum",
+            },
+            Field {
+                name: "params",
+                ty: "RawBytes",
+            },
+            Field {
+                name: "value",
+                ty: "TokenAmount",
+            },
+        ],
+    },
+    TypeDef {
+        name: "SetMinerMethodParams",
+        encoding: Encoding::Tuple,
+        fields: &[
+            Field {
+                name: "method",
+                ty: "MethodNum",
+            },
+            Field {
+                name: "allowed",
+                ty: "bool",
+            },
+        ],
+    },
+    TypeDef {
+        name: "events::GetEventsParams",
+        encoding: Encoding::Tuple,
+        fields: &[
+            Field {
+                name: "start",
+                ty: "u64",
+            },
+            Field {
+                name: "limit",
+                ty: "u64",
+            },
+        ],
+    },
+    TypeDef {
+        name: "events::GetEventsReturn",
+        encoding: Encoding::Tuple,
+        fields: &[
+            Field {
+                name: "count",
+                ty: "u64",
+            },
+            Field {
+                name: "events",
+                ty: "Vec<events::Event>",
+            },
+        ],
+    },
+    TypeDef {
+        name: "events::Event",
+        encoding: Encoding::Tuple,
+        fields: &[
+            Field {
+                name: "epoch",
+                ty: "ChainEpoch",
+            },
+            Field {
+                name: "caller",
+                ty: "ActorID",
+            },
+            Field {
+                name: "payload",
+                ty: "events::EventPayload",
+            },
+        ],
+    },
+    TypeDef {
+        name: "events::EventPayload",
+        encoding: Encoding::Enum,
+        fields: &[
+            Field {
+                name: "SayHello",
+                ty: "(u64)",
+            },
+            Field {
+                name: "Withdraw",
+                ty: "(Address, TokenAmount)",
+            },
+            Field {
+                name: "FundPower",
+                ty: "(TokenAmount)",
+            },
+            Field {
+                name: "MinerCreated",
+                ty: "(Address, Address)",
+            },
+            Field {
+                name: "OwnerChanged",
+                ty: "(Address, Address)",
+            },
+            Field {
+                name: "WorkerChanged",
+                ty: "(Address, Address)",
+            },
+            Field {
+                name: "MinerWithdrawn",
+                ty: "(Address, TokenAmount, TokenAmount)",
+            },
+            Field {
+                name: "MinerCalled",
+                ty: "(Address, MethodNum, TokenAmount)",
+            },
+            Field {
+                name: "MinerMethodAllowed",
+                ty: "(MethodNum, bool)",
+            },
+            Field {
+                name: "TxProposed",
+                ty: "(u64)",
+            },
+            Field {
+                name: "TxApproved",
+                ty: "(u64)",
+            },
+            Field {
+                name: "TxCancelled",
+                ty: "(u64)",
+            },
+            Field {
+                name: "SignerAdded",
+                ty: "(ActorID)",
+            },
+            Field {
+                name: "SignerRemoved",
+                ty: "(ActorID)",
+            },
+            Field {
+                name: "ThresholdChanged",
+                ty: "(u64)",
+            },
+            Field {
+                name: "ActionQueued",
+                ty: "(u64, ChainEpoch)",
+            },
+            Field {
+                name: "ActionCancelled",
+                ty: "(u64)",
+            },
+            Field {
+                name: "ActionExecuted",
+                ty: "(u64)",
+            },
+            Field {
+                name: "BeneficiaryChanged",
+                ty: "(Address)",
+            },
+            Field {
+                name: "DelayChanged",
+                ty: "(ChainEpoch)",
+            },
+            Field {
+                name: "RateLimitsChanged",
+                ty: "()",
+            },
+            Field {
+                name: "GuardianChanged",
+                ty: "(ActorID)",
+            },
+            Field {
+                name: "PausedChanged",
+                ty: "(bool)",
+            },
+            Field {
+                name: "Transferred",
+                ty: "(Address, MethodNum, TokenAmount)",
+            },
+            Field {
+                name: "MinerFunded",
+                ty: "(Address, TokenAmount)",
+            },
+            Field {
+                name: "PledgeAdded",
+                ty: "(Address, TokenAmount)",
+            },
+            Field {
+                name: "TransferCodeAllowed",
+                ty: "(Cid, bool)",
+            },
+            Field {
+                name: "DebtRepaid",
+                ty: "(Address, TokenAmount)",
+            },
+            Field {
+                name: "EscrowAdded",
+                ty: "(Address, TokenAmount)",
+            },
+            Field {
+                name: "EscrowWithdrawn",
+                ty: "(Address, TokenAmount, TokenAmount)",
+            },
+            Field {
+                name: "DatacapReceived",
+                ty: "(ActorID, TokenAmount)",
+            },
+            Field {
+                name: "DatacapAllocated",
+                ty: "(ActorID, TokenAmount)",
+            },
+            Field {
+                name: "DatacapTransferred",
+                ty: "(ActorID, Address, TokenAmount)",
+            },
+            Field {
+                name: "SharesMinted",
+                ty: "(ActorID, TokenAmount, TokenAmount)",
+            },
+            Field {
+                name: "SharesTransferred",
+                ty: "(ActorID, ActorID, TokenAmount)",
+            },
+            Field {
+                name: "SharesBurnt",
+                ty: "(ActorID, TokenAmount)",
+            },
+            Field {
+                name: "TransferLimitChanged",
+                ty: "(TokenAmount)",
+            },
+        ],
+    },
+    TypeDef {
+        name: "multisig::ProposeParams",
+        encoding: Encoding::Tuple,
+        fields: &[Field {
+            name: "op",
+            ty: "multisig::Operation",
+        }],
+    },
+    TypeDef {
+        name: "multisig::ProposeReturn",
+        encoding: Encoding::Tuple,
+        fields: &[
+            Field {
+                name: "tx_id",
+                ty: "u64",
+            },
+            Field {
+                name: "applied",
+                ty: "bool",
+            },
+            Field {
+                name: "ret",
+                ty: "RawBytes",
+            },
+        ],
+    },
+    TypeDef {
+        name: "multisig::TxnIDParams",
+        encoding: Encoding::Tuple,
+        fields: &[Field {
+            name: "tx_id",
+            ty: "u64",
+        }],
+    },
+    TypeDef {
+        name: "multisig::ApproveReturn",
+        encoding: Encoding::Tuple,
+        fields: &[
+            Field {
+                name: "applied",
+                ty: "bool",
+            },
+            Field {
+                name: "ret",
+                ty: "RawBytes",
+            },
+        ],
+    },
+    TypeDef {
+        name: "multisig::Transaction",
+        encoding: Encoding::Tuple,
+        fields: &[
+            Field {
+                name: "op",
+                ty: "multisig::Operation",
+            },
+            Field {
+                name: "proposer",
+                ty: "ActorID",
+            },
+            Field {
+                name: "approved",
+                ty: "Vec<ActorID>",
+            },
+        ],
+    },
+    TypeDef {
+        name: "multisig::Operation",
+        encoding: Encoding::Enum,
+        fields: &[
+            Field {
+                name: "Withdraw",
+                ty: "(Address, TokenAmount)",
+            },
+            Field {
+                name: "TakeOwner",
+                ty: "(Address)",
+            },
+            Field {
+                name: "ChangeWorker",
+                ty: "(Address, Address)",
+            },
+            Field {
+                name: "WithdrawMiner",
+                ty: "(Address, TokenAmount)",
+            },
+            Field {
+                name: "Destruct",
+                ty: "()",
+            },
+            Field {
+                name: "AddSigner",
+                ty: "(ActorID)",
+            },
+            Field {
+                name: "RemoveSigner",
+                ty: "(ActorID)",
+            },
+            Field {
+                name: "ChangeThreshold",
+                ty: "(u64)",
+            },
+            Field {
+                name: "SetMinerMethod",
+                ty: "(MethodNum, bool)",
+            },
+            Field {
+                name: "Transfer",
+                ty: "(Address, TokenAmount, MethodNum, RawBytes)",
+            },
+            Field {
+                name: "SetTransferLimit",
+                ty: "(TokenAmount)",
+            },
+        ],
+    },
+    TypeDef {
+        name: "timelock::QueueParams",
+        encoding: Encoding::Tuple,
+        fields: &[Field {
+            name: "action",
+            ty: "timelock::TimelockAction",
+        }],
+    },
+    TypeDef {
+        name: "timelock::QueueReturn",
+        encoding: Encoding::Tuple,
+        fields: &[
+            Field {
+                name: "id",
+                ty: "u64",
+            },
+            Field {
+                name: "eta",
+                ty: "ChainEpoch",
+            },
+        ],
+    },
+    TypeDef {
+        name: "timelock::QueuedIDParams",
+        encoding: Encoding::Tuple,
+        fields: &[Field {
+            name: "id",
+            ty: "u64",
+        }],
+    },
+    TypeDef {
+        name: "timelock::QueuedAction",
+        encoding: Encoding::Tuple,
+        fields: &[
+            Field {
+                name: "action",
+                ty: "timelock::TimelockAction",
+            },
+            Field {
+                name: "eta",
+                ty: "ChainEpoch",
+            },
+        ],
+    },
+    TypeDef {
+        name: "timelock::TimelockAction",
+        encoding: Encoding::Enum,
+        fields: &[
+            Field {
+                name: "ChangeWorker",
+                ty: "(Address, Address)",
+            },
+            Field {
+                name: "SetMinerMethod",
+                ty: "(MethodNum, bool)",
+            },
+            Field {
+                name: "SetBeneficiary",
+                ty: "(Address)",
+            },
+            Field {
+                name: "SetDelay",
+                ty: "(ChainEpoch)",
+            },
+            Field {
+                name: "SetRateLimits",
+                ty: "(Option<ratelimit::RateLimit>, Option<ratelimit::RateLimit>)",
+            },
+            Field {
+                name: "SetGuardian",
+                ty: "(ActorID)",
+            },
+            Field {
+                name: "SetTransferCode",
+                ty: "(Cid, bool)",
+            },
+        ],
+    },
+    TypeDef {
+        name: "ratelimit::AllowanceParams",
+        encoding: Encoding::Tuple,
+        fields: &[Field {
+            name: "caller",
+            ty: "ActorID",
+        }],
+    },
+    TypeDef {
+        name: "ratelimit::AllowanceReturn",
+        encoding: Encoding::Tuple,
+        fields: &[
+            Field {
+                name: "global",
+                ty: "Option<TokenAmount>",
+            },
+            Field {
+                name: "caller",
+                ty: "Option<TokenAmount>",
+            },
+        ],
+    },
+    TypeDef {
+        name: "ratelimit::RateLimit",
+        encoding: Encoding::Tuple,
+        fields: &[
+            Field {
+                name: "max_amount",
+                ty: "TokenAmount",
+            },
+            Field {
+                name: "period",
+                ty: "ChainEpoch",
+            },
+        ],
+    },
+    TypeDef {
+        name: "ratelimit::Bucket",
+        encoding: Encoding::Tuple,
+        fields: &[
+            Field {
+                name: "start",
+                ty: "ChainEpoch",
+            },
+            Field {
+                name: "spent",
+                ty: "TokenAmount",
+            },
+        ],
+    },
+    TypeDef {
+        name: "pause::SetPausedParams",
+        encoding: Encoding::Tuple,
+        fields: &[Field {
+            name: "paused",
+            ty: "bool",
+        }],
+    },
+    TypeDef {
+        name: "transfer::TransferParams",
+        encoding: Encoding::Tuple,
+        fields: &[
+            Field {
+                name: "to",
+                ty: "Address",
+            },
+            Field {
+                name: "amount",
+                ty: "TokenAmount",
+            },
+            Field {
+                name: "method",
+                ty: "MethodNum",
+            },
+            Field {
+                name: "params",
+                ty: "RawBytes",
+            },
+        ],
+    },
+    TypeDef {
+        name: "transfer::FundMinerParams",
+        encoding: Encoding::Tuple,
+        fields: &[
+            Field {
+                name: "miner",
+                ty: "Address",
+            },
+            Field {
+                name: "amount",
+                ty: "TokenAmount",
+            },
+        ],
+    },
+    TypeDef {
+        name: "miner::RepayDebtParams",
+        encoding: Encoding::Tuple,
+        fields: &[
+            Field {
+                name: "miner",
+                ty: "Address",
+            },
+            Field {
+                name: "amount",
+                ty: "TokenAmount",
+            },
+        ],
+    },
+    TypeDef {
+        name: "miner::TopUpPledgeParams",
+        encoding: Encoding::Tuple,
+        fields: &[
+            Field {
+                name: "miner",
+                ty: "Address",
+            },
+            Field {
+                name: "miner_state",
+                ty: "Cid",
+            },
+            Field {
+                name: "amount",
+                ty: "TokenAmount",
+            },
+        ],
+    },
+    TypeDef {
+        name: "miner::MinerFundsParams",
+        encoding: Encoding::Tuple,
+        fields: &[Field {
+            name: "miner_states",
+            ty: "Vec<Cid>",
+        }],
+    },
+    TypeDef {
+        name: "miner::MinerFunds",
+        encoding: Encoding::Tuple,
+        fields: &[
+            Field {
+                name: "miner",
+                ty: "Address",
+            },
+            Field {
+                name: "fee_debt",
+                ty: "TokenAmount",
+            },
+            Field {
+                name: "locked_funds",
+                ty: "TokenAmount",
+            },
+            Field {
+                name: "initial_pledge",
+                ty: "TokenAmount",
+            },
+            Field {
+                name: "pre_commit_deposits",
+                ty: "TokenAmount",
+            },
+        ],
+    },
+    TypeDef {
+        name: "miner::TerminateSectorsParams",
+        encoding: Encoding::Tuple,
+        fields: &[Field {
+            name: "terminations",
+            ty: "Vec<miner::TerminationDeclaration>",
+        }],
+    },
+    TypeDef {
+        name: "miner::TerminationDeclaration",
+        encoding: Encoding::Tuple,
+        fields: &[
+            Field {
+                name: "deadline",
+                ty: "u64",
+            },
+            Field {
+                name: "partition",
+                ty: "u64",
+            },
+            Field {
+                name: "sectors",
+                ty: "BitField",
+            },
+        ],
+    },
+    TypeDef {
+        name: "miner::TerminateSectorsReturn",
+        encoding: Encoding::Tuple,
+        fields: &[Field {
+            name: "done",
+            ty: "bool",
+        }],
+    },
+    TypeDef {
+        name: "miner::DeclareFaultsParams",
+        encoding: Encoding::Tuple,
+        fields: &[Field {
+            name: "faults",
+            ty: "Vec<miner::FaultDeclaration>",
+        }],
+    },
+    TypeDef {
+        name: "miner::FaultDeclaration",
+        encoding: Encoding::Tuple,
+        fields: &[
+            Field {
+                name: "deadline",
+                ty: "u64",
+            },
+            Field {
+                name: "partition",
+                ty: "u64",
+            },
+            Field {
+                name: "sectors",
+                ty: "BitField",
+            },
+        ],
+    },
+    TypeDef {
+        name: "miner::DeclareFaultsRecoveredParams",
+        encoding: Encoding::Tuple,
+        fields: &[Field {
+            name: "recoveries",
+            ty: "Vec<miner::RecoveryDeclaration>",
+        }],
+    },
+    TypeDef {
+        name: "miner::RecoveryDeclaration",
+        encoding: Encoding::Tuple,
+        fields: &[
+            Field {
+                name: "deadline",
+                ty: "u64",
+            },
+            Field {
+                name: "partition",
+                ty: "u64",
+            },
+            Field {
+                name: "sectors",
+                ty: "BitField",
+            },
+        ],
+    },
+    TypeDef {
+        name: "miner::ExtendSectorExpirationParams",
+        encoding: Encoding::Tuple,
+        fields: &[Field {
+            name: "extensions",
+            ty: "Vec<miner::ExpirationExtension>",
+        }],
+    },
+    TypeDef {
+        name: "miner::ExpirationExtension",
+        encoding: Encoding::Tuple,
+        fields: &[
+            Field {
+                name: "deadline",
+                ty: "u64",
+            },
+            Field {
+                name: "partition",
+                ty: "u64",
+            },
+            Field {
+                name: "sectors",
+                ty: "BitField",
+            },
+            Field {
+                name: "new_expiration",
+                ty: "ChainEpoch",
+            },
+        ],
+    },
+    TypeDef {
+        name: "miner::TerminateSectorsReq",
+        encoding: Encoding::Tuple,
+        fields: &[
+            Field {
+                name: "miner",
+                ty: "Address",
+            },
+            Field {
+                name: "params",
+                ty: "miner::TerminateSectorsParams",
+            },
+        ],
+    },
+    TypeDef {
+        name: "miner::DeclareFaultsReq",
+        encoding: Encoding::Tuple,
+        fields: &[
+            Field {
+                name: "miner",
+                ty: "Address",
+            },
+            Field {
+                name: "params",
+                ty: "miner::DeclareFaultsParams",
+            },
+        ],
+    },
+    TypeDef {
+        name: "miner::DeclareFaultsRecoveredReq",
+        encoding: Encoding::Tuple,
+        fields: &[
+            Field {
+                name: "miner",
+                ty: "Address",
+            },
+            Field {
+                name: "params",
+                ty: "miner::DeclareFaultsRecoveredParams",
+            },
+        ],
+    },
+    TypeDef {
+        name: "miner::ExtendSectorExpirationReq",
+        encoding: Encoding::Tuple,
+        fields: &[
+            Field {
+                name: "miner",
+                ty: "Address",
+            },
+            Field {
+                name: "params",
+                ty: "miner::ExtendSectorExpirationParams",
+            },
+        ],
+    },
+    TypeDef {
+        name: "market::EscrowParams",
+        encoding: Encoding::Tuple,
+        fields: &[
+            Field {
+                name: "miner",
+                ty: "Address",
+            },
+            Field {
+                name: "amount",
+                ty: "TokenAmount",
+            },
+        ],
+    },
+    TypeDef {
+        name: "market::MarketBalanceParams",
+        encoding: Encoding::Tuple,
+        fields: &[
+            Field {
+                name: "market_state",
+                ty: "Cid",
+            },
+            Field {
+                name: "miner",
+                ty: "Address",
+            },
+        ],
+    },
+    TypeDef {
+        name: "market::MarketBalance",
+        encoding: Encoding::Tuple,
+        fields: &[
+            Field {
+                name: "escrow",
+                ty: "TokenAmount",
+            },
+            Field {
+                name: "locked",
+                ty: "TokenAmount",
+            },
+        ],
+    },
+    TypeDef {
+        name: "datacap::AllocateParams",
+        encoding: Encoding::Tuple,
+        fields: &[Field {
+            name: "allocations",
+            ty: "Vec<datacap::AllocationRequest>",
+        }],
+    },
+    TypeDef {
+        name: "datacap::AllocationRequest",
+        encoding: Encoding::Tuple,
+        fields: &[
+            Field {
+                name: "provider",
+                ty: "ActorID",
+            },
+            Field {
+                name: "data",
+                ty: "Cid",
+            },
+            Field {
+                name: "size",
+                ty: "PaddedPieceSize",
+            },
+            Field {
+                name: "term_min",
+                ty: "ChainEpoch",
+            },
+            Field {
+                name: "term_max",
+                ty: "ChainEpoch",
+            },
+            Field {
+                name: "expiration",
+                ty: "ChainEpoch",
+            },
+        ],
+    },
+    TypeDef {
+        name: "datacap::TransferDatacapParams",
+        encoding: Encoding::Tuple,
+        fields: &[
+            Field {
+                name: "to",
+                ty: "Address",
+            },
+            Field {
+                name: "amount",
+                ty: "TokenAmount",
+            },
+        ],
+    },
+    TypeDef {
+        name: "datacap::DatacapBalanceParams",
+        encoding: Encoding::Tuple,
+        fields: &[Field {
+            name: "depositor",
+            ty: "ActorID",
+        }],
+    },
+    TypeDef {
+        name: "datacap::UniversalReceiverParams",
+        encoding: Encoding::Tuple,
+        fields: &[
+            Field {
+                name: "type_",
+                ty: "u32",
+            },
+            Field {
+                name: "payload",
+                ty: "RawBytes",
+            },
+        ],
+    },
+    TypeDef {
+        name: "token::TransferParams",
+        encoding: Encoding::Tuple,
+        fields: &[
+            Field {
+                name: "to",
+                ty: "Address",
+            },
+            Field {
+                name: "amount",
+                ty: "TokenAmount",
+            },
+            Field {
+                name: "operator_data",
+                ty: "RawBytes",
+            },
+        ],
+    },
+    TypeDef {
+        name: "token::TransferReturn",
+        encoding: Encoding::Tuple,
+        fields: &[
+            Field {
+                name: "from_balance",
+                ty: "TokenAmount",
+            },
+            Field {
+                name: "to_balance",
+                ty: "TokenAmount",
+            },
+            Field {
+                name: "recipient_data",
+                ty: "RawBytes",
+            },
+        ],
+    },
+    TypeDef {
+        name: "token::TransferFromParams",
+        encoding: Encoding::Tuple,
+        fields: &[
+            Field {
+                name: "from",
+                ty: "Address",
+            },
+            Field {
+                name: "to",
+                ty: "Address",
+            },
+            Field {
+                name: "amount",
+                ty: "TokenAmount",
+            },
+            Field {
+                name: "operator_data",
+                ty: "RawBytes",
+            },
+        ],
+    },
+    TypeDef {
+        name: "token::TransferFromReturn",
+        encoding: Encoding::Tuple,
+        fields: &[
+            Field {
+                name: "from_balance",
+                ty: "TokenAmount",
+            },
+            Field {
+                name: "to_balance",
+                ty: "TokenAmount",
+            },
+            Field {
+                name: "allowance",
+                ty: "TokenAmount",
+            },
+            Field {
+                name: "recipient_data",
+                ty: "RawBytes",
+            },
+        ],
+    },
+    TypeDef {
+        name: "token::IncreaseAllowanceParams",
+        encoding: Encoding::Tuple,
+        fields: &[
+            Field {
+                name: "operator",
+                ty: "Address",
+            },
+            Field {
+                name: "increase",
+                ty: "TokenAmount",
+            },
+        ],
+    },
+    TypeDef {
+        name: "token::DecreaseAllowanceParams",
+        encoding: Encoding::Tuple,
+        fields: &[
+            Field {
+                name: "operator",
+                ty: "Address",
+            },
+            Field {
+                name: "decrease",
+                ty: "TokenAmount",
+            },
+        ],
+    },
+    TypeDef {
+        name: "token::RevokeAllowanceParams",
+        encoding: Encoding::Tuple,
+        fields: &[Field {
+            name: "operator",
+            ty: "Address",
+        }],
+    },
+    TypeDef {
+        name: "token::BurnParams",
+        encoding: Encoding::Tuple,
+        fields: &[Field {
+            name: "amount",
+            ty: "TokenAmount",
+        }],
+    },
+    TypeDef {
+        name: "token::BurnReturn",
+        encoding: Encoding::Tuple,
+        fields: &[Field {
+            name: "balance",
+            ty: "TokenAmount",
+        }],
+    },
+];
+
+/// Names of the types referenced by `ty`, such as `Address` and `TokenAmount`
+/// for `(Address, TokenAmount)`.
+pub fn referenced_types(ty: &str) -> impl Iterator<Item = &str> {
+    ty.split(|c: char| "()<>, ".contains(c))
+        .filter(|name| !name.is_empty() && *name != "Option" && *name != "Vec")
+}
+
+fn json_string(out: &mut String, s: &str) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+fn json_opt_string(out: &mut String, s: Option<&str>) {
+    match s {
+        Some(s) => json_string(out, s),
+        None => out.push_str("null"),
+    }
+}
+
+/// The interface as JSON: `{"methods": [...], "types": [...]}`.
+pub fn to_json() -> String {
+    let mut out = String::from("{\n  \"methods\": [");
+    for (i, method) in METHODS.iter().enumerate() {
+        out.push_str(if i == 0 { "\n    " } else { ",\n    " });
+        out.push_str(&format!("{{\"number\": {}, \"name\": ", method.number()));
+        json_string(&mut out, method.name);
+        match method.hashed {
+            Some(number) => out.push_str(&format!(", \"frc42\": {}", number)),
+            None => out.push_str(", \"frc42\": null"),
+        }
+        out.push_str(", \"params\": ");
+        json_opt_string(&mut out, method.params);
+        out.push_str(", \"return\": ");
+        json_opt_string(&mut out, method.ret);
+        out.push_str(", \"doc\": ");
+        json_opt_string(&mut out, method.doc());
+        out.push('}');
+    }
+    out.push_str("\n  ],\n  \"types\": [");
+    for (i, def) in TYPES.iter().enumerate() {
+        out.push_str(if i == 0 { "\n    " } else { ",\n    " });
+        out.push_str("{\"name\": ");
+        json_string(&mut out, def.name);
+        out.push_str(&format!(
+            ", \"encoding\": \"{:?}\", \"fields\": [",
+            def.encoding
+        ));
+        for (j, field) in def.fields.iter().enumerate() {
+            if j > 0 {
+                out.push_str(", ");
+            }
+            out.push_str("{\"name\": ");
+            json_string(&mut out, field.name);
+            out.push_str(", \"type\": ");
+            json_string(&mut out, field.ty);
+            out.push('}');
+        }
+        out.push_str("]}");
+    }
+    out.push_str("\n  ]\n}\n");
+    out
+}
+
+#[cfg(test)]
+mod test {
+    #[test]
+    fn abi_types_are_described() {
+        use super::{referenced_types, EXTERNAL_TYPES, METHODS, TYPES};
+
+        // Every type referenced is described, or comes from the FVM crates.
+        let fields = TYPES.iter().flat_map(|def| def.fields.iter().map(|f| f.ty));
+        let signatures = METHODS
+            .iter()
+            .flat_map(|method| method.params.into_iter().chain(method.ret));
+        for ty in fields.chain(signatures).flat_map(referenced_types) {
+            assert!(
+                EXTERNAL_TYPES.contains(&ty) || TYPES.iter().any(|def| def.name == ty),
+                "type {} is not described",
+                ty
+            );
+        }
+    }
+
+    #[test]
+    fn abi_docs_name_exported_methods() {
+        use super::{METHODS, METHOD_DOCS};
+
+        for (name, _) in METHOD_DOCS {
+            assert!(
+                METHODS.iter().any(|method| method.name == *name),
+                "documented method {} is not exported",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn abi_lists_enum_variants_by_index() {
+        use super::{Encoding, TYPES};
+        use crate::events::EventPayload;
+        use crate::multisig::Operation;
+        use crate::timelock::TimelockAction;
+
+        let enums: [(&str, &[(u64, &str)]); 3] = [
+            ("events::EventPayload", EventPayload::VARIANTS),
+            ("multisig::Operation", Operation::VARIANTS),
+            ("timelock::TimelockAction", TimelockAction::VARIANTS),
+        ];
+        for (name, variants) in enums {
+            let def = TYPES.iter().find(|def| def.name == name).unwrap();
+            assert_eq!(def.encoding, Encoding::Enum, "{}", name);
+            let listed: Vec<(u64, &str)> = def
+                .fields
+                .iter()
+                .enumerate()
+                .map(|(index, field)| (index as u64, field.name))
+                .collect();
+            assert_eq!(listed, variants, "{}", name);
+        }
+        assert_eq!(
+            TYPES
+                .iter()
+                .filter(|def| def.encoding == Encoding::Enum)
+                .count(),
+            enums.len()
+        );
+    }
+}
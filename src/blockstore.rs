@@ -1,15 +1,20 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
 use std::convert::TryFrom;
 
 use anyhow::{anyhow, Result};
 use cid::multihash::Code;
 use cid::Cid;
-use fvm_ipld_blockstore::Block;
+use fvm_ipld_blockstore::{Block, Buffered};
+use fvm_ipld_encoding::DAG_CBOR;
 use fvm_sdk as sdk;
 
 /// A blockstore that delegates to IPLD syscalls.
-pub struct Blockstore;
+#[derive(Default)]
+#[cfg_attr(not(target_arch = "wasm32"), allow(dead_code))]
+pub struct SyscallBlockstore;
 
-impl fvm_ipld_blockstore::Blockstore for Blockstore {
+impl fvm_ipld_blockstore::Blockstore for SyscallBlockstore {
     fn get(&self, cid: &Cid) -> Result<Option<Vec<u8>>> {
         // If this fails, the _CID_ is invalid. I.e., we have a bug.
         sdk::ipld::get(cid)
@@ -18,7 +23,8 @@ impl fvm_ipld_blockstore::Blockstore for Blockstore {
     }
 
     fn put_keyed(&self, k: &Cid, block: &[u8]) -> Result<()> {
-        let code = Code::try_from(k.hash().code()).map_err(|e| anyhow!(e.to_string()))?;
+        let code = Code::try_from(k.hash().code())
+            .map_err(|_| anyhow!("unsupported multihash code {:#x}", k.hash().code()))?;
         let k2 = self.put(code, &Block::new(k.codec(), block))?;
         if k != &k2 {
             return Err(anyhow!("put block with cid {} but has cid {}", k, k2));
@@ -30,11 +36,340 @@ impl fvm_ipld_blockstore::Blockstore for Blockstore {
     where
         D: AsRef<[u8]>,
     {
-        // TODO: Don't hard-code the size. Unfortunately, there's no good way to get it from the
-        //  codec at the moment.
-        const SIZE: u32 = 32;
-        let k = sdk::ipld::put(code.into(), SIZE, block.codec, block.data.as_ref())
+        let size = digest_size(code, block.data.as_ref())?;
+        let k = sdk::ipld::put(code.into(), size, block.codec, block.data.as_ref())
             .map_err(|e| anyhow!("put failed with {:?}", e))?;
         Ok(k)
     }
 }
+
+/// Returns the length in bytes of the `code` digest of `data`.
+pub fn digest_size(code: Code, data: &[u8]) -> Result<u32> {
+    /// The largest digest a multihash can hold.
+    const MAX_DIGEST_SIZE: usize = 64;
+
+    let size = match code {
+        Code::Ripemd160 => 20,
+        Code::Sha3_224 | Code::Keccak224 => 28,
+        Code::Sha2_256 | Code::Sha3_256 | Code::Keccak256 | Code::Blake2b256 | Code::Ripemd256 => {
+            32
+        }
+        Code::Ripemd320 => 40,
+        Code::Sha3_384 | Code::Keccak384 => 48,
+        Code::Sha2_512 | Code::Sha3_512 | Code::Keccak512 | Code::Blake2b512 => 64,
+        // The identity "digest" is the data itself.
+        Code::Identity if data.len() <= MAX_DIGEST_SIZE => data.len() as u32,
+        Code::Identity => {
+            return Err(anyhow!(
+                "block of {} bytes is too large for an identity CID",
+                data.len()
+            ))
+        }
+        // Hashes enabled through multihash features of other crates.
+        #[allow(unreachable_patterns)]
+        _ => return Err(anyhow!("unsupported multihash code {:?}", code)),
+    };
+    Ok(size)
+}
+
+struct CachedBlock {
+    data: Vec<u8>,
+    /// Whether the block was written through the cache and not flushed yet.
+    dirty: bool,
+}
+
+/// A blockstore that keeps every block read or written in memory, and only
+/// writes to `inner` on [`Buffered::flush`].
+///
+/// HAMTs and AMTs write a new root (and path) on every change, so a message
+/// touching a map several times produces intermediate nodes nobody will ever
+/// read again. Flushing only the blocks reachable from the final root skips
+/// those writes entirely.
+pub struct CachingBlockstore<BS> {
+    inner: BS,
+    cache: RefCell<HashMap<Cid, CachedBlock>>,
+}
+
+impl<BS> CachingBlockstore<BS>
+where
+    BS: fvm_ipld_blockstore::Blockstore,
+{
+    pub fn new(inner: BS) -> Self {
+        Self {
+            inner,
+            cache: Default::default(),
+        }
+    }
+
+    pub fn inner(&self) -> &BS {
+        &self.inner
+    }
+
+    /// Writes the dirty blocks reachable from `cid`, children first so that
+    /// every link of a written block is already in `inner`. Blocks that are
+    /// not dirty are already in `inner` together with everything they link to.
+    fn flush_block(&self, cid: &Cid) -> Result<()> {
+        let data = match self.cache.borrow().get(cid) {
+            Some(block) if block.dirty => block.data.clone(),
+            _ => return Ok(()),
+        };
+        if cid.codec() == DAG_CBOR {
+            for link in links(&data)? {
+                self.flush_block(&link)?;
+            }
+        }
+        self.inner.put_keyed(cid, &data)?;
+        if let Some(block) = self.cache.borrow_mut().get_mut(cid) {
+            block.dirty = false;
+        }
+        Ok(())
+    }
+}
+
+impl<BS> fvm_ipld_blockstore::Blockstore for CachingBlockstore<BS>
+where
+    BS: fvm_ipld_blockstore::Blockstore,
+{
+    fn get(&self, cid: &Cid) -> Result<Option<Vec<u8>>> {
+        if let Some(block) = self.cache.borrow().get(cid) {
+            return Ok(Some(block.data.clone()));
+        }
+        let data = self.inner.get(cid)?;
+        if let Some(data) = &data {
+            self.cache.borrow_mut().insert(
+                *cid,
+                CachedBlock {
+                    data: data.clone(),
+                    dirty: false,
+                },
+            );
+        }
+        Ok(data)
+    }
+
+    fn has(&self, cid: &Cid) -> Result<bool> {
+        if self.cache.borrow().contains_key(cid) {
+            return Ok(true);
+        }
+        self.inner.has(cid)
+    }
+
+    fn put_keyed(&self, k: &Cid, block: &[u8]) -> Result<()> {
+        self.cache
+            .borrow_mut()
+            .entry(*k)
+            .or_insert_with(|| CachedBlock {
+                data: block.to_vec(),
+                dirty: true,
+            });
+        Ok(())
+    }
+
+    fn put<D>(&self, code: Code, block: &Block<D>) -> Result<Cid>
+    where
+        D: AsRef<[u8]>,
+    {
+        let k = block.cid(code);
+        self.put_keyed(&k, block.as_ref())?;
+        Ok(k)
+    }
+}
+
+impl<BS> Buffered for CachingBlockstore<BS>
+where
+    BS: fvm_ipld_blockstore::Blockstore,
+{
+    fn flush(&self, root: &Cid) -> Result<()> {
+        self.flush_block(root)
+    }
+}
+
+/// Where the actor's blocks end up: the state tree when running in the FVM,
+/// and plain memory in native builds so that state logic can be unit tested.
+#[cfg(target_arch = "wasm32")]
+type Backing = SyscallBlockstore;
+#[cfg(not(target_arch = "wasm32"))]
+type Backing = fvm_ipld_blockstore::MemoryBlockstore;
+
+thread_local! {
+    static CACHE: CachingBlockstore<Backing> = CachingBlockstore::new(Backing::default());
+}
+
+/// The actor's blockstore: a handle to a [`CachingBlockstore`] that lives for
+/// the current message. Blocks written through it only reach the backing
+/// store once flushed from a root.
+pub struct Blockstore;
+
+impl fvm_ipld_blockstore::Blockstore for Blockstore {
+    fn get(&self, cid: &Cid) -> Result<Option<Vec<u8>>> {
+        CACHE.with(|bs| bs.get(cid))
+    }
+
+    fn has(&self, cid: &Cid) -> Result<bool> {
+        CACHE.with(|bs| bs.has(cid))
+    }
+
+    fn put_keyed(&self, k: &Cid, block: &[u8]) -> Result<()> {
+        CACHE.with(|bs| bs.put_keyed(k, block))
+    }
+
+    fn put<D>(&self, code: Code, block: &Block<D>) -> Result<Cid>
+    where
+        D: AsRef<[u8]>,
+    {
+        CACHE.with(|bs| bs.put(code, block))
+    }
+}
+
+impl Buffered for Blockstore {
+    fn flush(&self, root: &Cid) -> Result<()> {
+        CACHE.with(|bs| bs.flush(root))
+    }
+}
+
+/// Returns the CIDs linked from a DAG-CBOR block, i.e. the byte strings
+/// tagged 42.
+pub fn links(block: &[u8]) -> Result<Vec<Cid>> {
+    const CID_TAG: u64 = 42;
+
+    let mut reader = block;
+    let mut links = Vec::new();
+    // Number of data items still to read.
+    let mut remaining: u64 = 1;
+    while remaining > 0 {
+        remaining -= 1;
+        let (major, value) = read_header(&mut reader)?;
+        match major {
+            // Integers and simple values/floats, whose payload is the header.
+            0 | 1 | 7 => {}
+            // Byte and text strings.
+            2 | 3 => {
+                read_bytes(&mut reader, value)?;
+            }
+            // A bogus length makes us run out of bytes rather than overflow.
+            4 => remaining = remaining.saturating_add(value),
+            5 => remaining = remaining.saturating_add(value.saturating_mul(2)),
+            6 if value == CID_TAG => {
+                let (major, len) = read_header(&mut reader)?;
+                let bytes = read_bytes(&mut reader, len)?;
+                match (major, bytes.split_first()) {
+                    (2, Some((0, cid))) => links.push(Cid::try_from(cid)?),
+                    _ => return Err(anyhow!("invalid link in block")),
+                }
+            }
+            6 => remaining = remaining.saturating_add(1),
+            _ => unreachable!(),
+        }
+    }
+    if !reader.is_empty() {
+        return Err(anyhow!("trailing bytes after block"));
+    }
+    Ok(links)
+}
+
+fn read_header(reader: &mut &[u8]) -> Result<(u8, u64)> {
+    let first = read_bytes(reader, 1)?[0];
+    let value = match first & 0x1f {
+        info @ 0..=23 => info as u64,
+        info @ 24..=27 => read_bytes(reader, 1 << (info - 24))?
+            .iter()
+            .fold(0, |acc, b| acc << 8 | *b as u64),
+        _ => return Err(anyhow!("unsupported CBOR header {:#x}", first)),
+    };
+    Ok((first >> 5, value))
+}
+
+fn read_bytes<'a>(reader: &mut &'a [u8], len: u64) -> Result<&'a [u8]> {
+    let len = usize::try_from(len).map_err(|_| anyhow!("CBOR item too long"))?;
+    if reader.len() < len {
+        return Err(anyhow!("unexpected end of block"));
+    }
+    let (bytes, rest) = reader.split_at(len);
+    *reader = rest;
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod test {
+    use cid::Cid;
+
+    #[test]
+    fn caching_blockstore_only_writes_the_final_tree() {
+        use fvm_ipld_blockstore::tracking::TrackingBlockstore;
+        use fvm_ipld_blockstore::{Blockstore, Buffered, MemoryBlockstore};
+        use fvm_ipld_hamt::Hamt;
+        use fvm_shared::HAMT_BIT_WIDTH;
+
+        // A message updating a map entry by entry, flushing after each change
+        // like map::set does.
+        fn workload<BS: Blockstore>(bs: &BS) -> Cid {
+            let mut root = Hamt::<_, u64>::new_with_bit_width(bs, HAMT_BIT_WIDTH)
+                .flush()
+                .unwrap();
+            for i in 0..200 {
+                let mut map =
+                    Hamt::<_, u64>::load_with_bit_width(&root, bs, HAMT_BIT_WIDTH).unwrap();
+                map.set(crate::map::u64_key(i), i).unwrap();
+                root = map.flush().unwrap();
+            }
+            root
+        }
+
+        let direct = TrackingBlockstore::new(MemoryBlockstore::new());
+        let root = workload(&direct);
+
+        let cached =
+            super::CachingBlockstore::new(TrackingBlockstore::new(MemoryBlockstore::new()));
+        assert_eq!(workload(&cached), root);
+        cached.flush(&root).unwrap();
+
+        // The IPLD syscalls charge gas per block and per byte, so these
+        // compare what the workload costs with and without the cache.
+        let direct = *direct.stats.borrow();
+        let stats = *cached.inner().stats.borrow();
+        assert!(stats.w * 4 < direct.w);
+        assert!(stats.bw * 4 < direct.bw);
+        assert_eq!(stats.r, 0);
+
+        // Everything reachable from the root made it to the inner store.
+        let map =
+            Hamt::<_, u64>::load_with_bit_width(&root, cached.inner(), HAMT_BIT_WIDTH).unwrap();
+        for i in 0..200 {
+            assert_eq!(map.get(&crate::map::u64_key(i)).unwrap(), Some(&i));
+        }
+    }
+
+    #[test]
+    fn dag_cbor_links_are_found() {
+        use fvm_ipld_encoding::to_vec;
+
+        let a = Cid::try_from("bafy2bzacea6bvgucghtd66eubqazpknwqqpfywtdgp5qxludjsa6tyd6cxwuy")
+            .unwrap();
+        let b = Cid::try_from("bafy2bzacece6ioczclolgntuhvnqseyloiz37gikrwyh2f6vbiwlw7etkll3q")
+            .unwrap();
+        let block = to_vec(&(1u64, vec![a], "x", (b, -5i64))).unwrap();
+        assert_eq!(super::links(&block).unwrap(), vec![a, b]);
+        assert!(super::links(&block[..block.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn digest_size_follows_the_hash_function() {
+        use super::digest_size;
+        use cid::multihash::{Code, MultihashDigest};
+
+        let data = b"digest me";
+        for code in [
+            Code::Blake2b256,
+            Code::Blake2b512,
+            Code::Sha2_256,
+            Code::Sha3_384,
+            Code::Keccak256,
+            Code::Ripemd160,
+            Code::Identity,
+        ] {
+            let size = digest_size(code, data).unwrap();
+            assert_eq!(size, code.digest(data).size() as u32, "{:?}", code);
+        }
+        assert!(digest_size(Code::Identity, &[0; 65]).is_err());
+    }
+}
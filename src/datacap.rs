@@ -0,0 +1,275 @@
+//! DataCap held on behalf of depositors. Depositors transfer DataCap to this
+//! actor through the datacap actor's FRC-46 interface, which calls our
+//! receiver hook; the actor then tracks each depositor's balance, and lets
+//! them allocate it to managed providers or transfer it out again.
+
+use cid::Cid;
+use frc42_dispatch::method_hash;
+use fvm_ipld_encoding::tuple::{Deserialize_tuple, Serialize_tuple};
+use fvm_ipld_encoding::RawBytes;
+use fvm_shared::address::Address;
+use fvm_shared::clock::ChainEpoch;
+use fvm_shared::econ::TokenAmount;
+use fvm_shared::piece::PaddedPieceSize;
+use fvm_shared::{ActorID, MethodNum};
+
+use crate::blockstore::Blockstore;
+use crate::events::EventPayload;
+use crate::{guard, map, runtime::rt, transfer, State};
+
+/// ID of the datacap actor.
+pub const DATACAP_ACTOR: ActorID = 7;
+
+/// ID of the verified registry actor.
+pub const VERIFIED_REGISTRY_ACTOR: ActorID = 6;
+
+/// FRC-42 method number of the datacap actor's `Transfer`.
+pub const DATACAP_TRANSFER: MethodNum = method_hash!("Transfer");
+
+/// FRC-42 method number of the FRC-46 receiver hook, `Receive`.
+pub const RECEIVER_HOOK: MethodNum = method_hash!("Receive");
+
+/// FRC-42 hash of "FRC46", the receiver type of FRC-46 token transfers.
+pub const FRC46_TOKEN_TYPE: u32 = method_hash!("FRC46") as u32;
+
+/// Parameters of the universal receiver hook.
+#[derive(Debug, Serialize_tuple, Deserialize_tuple)]
+pub struct UniversalReceiverParams {
+    pub type_: u32,
+    pub payload: RawBytes,
+}
+
+/// Payload of the receiver hook for FRC-46 token transfers.
+#[derive(Debug, Serialize_tuple, Deserialize_tuple)]
+pub struct FRC46TokenReceived {
+    pub from: ActorID,
+    pub to: ActorID,
+    pub operator: ActorID,
+    pub amount: TokenAmount,
+    pub operator_data: RawBytes,
+    pub token_data: RawBytes,
+}
+
+/// Parameters of the datacap actor's `Transfer`.
+#[derive(Debug, Serialize_tuple, Deserialize_tuple)]
+pub struct DatacapTransferParams {
+    pub to: Address,
+    pub amount: TokenAmount,
+    pub operator_data: RawBytes,
+}
+
+/// A request to the verified registry to allocate DataCap to a piece.
+#[derive(Clone, Debug, PartialEq, Serialize_tuple, Deserialize_tuple)]
+pub struct AllocationRequest {
+    pub provider: ActorID,
+    pub data: Cid,
+    pub size: PaddedPieceSize,
+    pub term_min: ChainEpoch,
+    pub term_max: ChainEpoch,
+    pub expiration: ChainEpoch,
+}
+
+/// A request to the verified registry to extend the term of a claim.
+#[derive(Clone, Debug, PartialEq, Serialize_tuple, Deserialize_tuple)]
+pub struct ClaimExtensionRequest {
+    pub provider: ActorID,
+    pub claim: u64,
+    pub term_max: ChainEpoch,
+}
+
+/// Operator data of a DataCap transfer to the verified registry.
+#[derive(Clone, Debug, PartialEq, Serialize_tuple, Deserialize_tuple)]
+pub struct AllocationRequests {
+    pub allocations: Vec<AllocationRequest>,
+    pub extensions: Vec<ClaimExtensionRequest>,
+}
+
+#[derive(Debug, Serialize_tuple, Deserialize_tuple)]
+pub struct AllocateParams {
+    pub allocations: Vec<AllocationRequest>,
+}
+
+#[derive(Debug, Serialize_tuple, Deserialize_tuple)]
+pub struct TransferDatacapParams {
+    pub to: Address,
+    pub amount: TokenAmount,
+}
+
+#[derive(Debug, Serialize_tuple, Deserialize_tuple)]
+pub struct DatacapBalanceParams {
+    pub depositor: ActorID,
+}
+
+/// DataCap tokens covering `size` bytes, one whole token per byte.
+pub fn datacap_for(size: PaddedPieceSize) -> TokenAmount {
+    TokenAmount::from_whole(size.0)
+}
+
+impl State {
+    /// DataCap held on behalf of `depositor`.
+    pub fn datacap_balance(&self, depositor: ActorID) -> TokenAmount {
+        match map::get(Blockstore, &self.datacap_balances, &map::u64_key(depositor)) {
+            Ok(balance) => balance.unwrap_or_else(|| TokenAmount::from_atto(0)),
+            Err(err) => abort!(USR_ILLEGAL_STATE, "{}", err),
+        }
+    }
+
+    pub(crate) fn set_datacap_balance(&mut self, depositor: ActorID, balance: TokenAmount) {
+        let key = map::u64_key(depositor);
+        let balances = if balance == TokenAmount::from_atto(0) {
+            map::remove::<TokenAmount>(Blockstore, &self.datacap_balances, &key)
+        } else {
+            map::set(Blockstore, &self.datacap_balances, key, balance)
+        };
+        self.datacap_balances = match balances {
+            Ok(balances) => balances,
+            Err(err) => abort!(USR_ILLEGAL_STATE, "{}", err),
+        };
+    }
+
+    /// Takes `amount` out of `depositor`'s DataCap balance, aborting if it
+    /// doesn't cover it.
+    pub(crate) fn debit_datacap(&mut self, depositor: ActorID, amount: &TokenAmount) {
+        let balance = self.datacap_balance(depositor);
+        if *amount < TokenAmount::from_atto(0) || balance < *amount {
+            abort!(
+                USR_INSUFFICIENT_FUNDS,
+                "f0{} holds {} DataCap, can't spend {}",
+                depositor,
+                balance,
+                amount
+            );
+        }
+        self.set_datacap_balance(depositor, balance - amount.clone());
+    }
+}
+
+/// Transfers DataCap held by this actor through the datacap actor. `state`
+/// must already be debited; it is saved along with the send.
+fn send_datacap(state: &mut State, to: Address, amount: TokenAmount, operator_data: RawBytes) {
+    let params = DatacapTransferParams {
+        to,
+        amount,
+        operator_data,
+    };
+    if let Err(err) = guard::send_locked(
+        state,
+        &Address::new_id(DATACAP_ACTOR),
+        DATACAP_TRANSFER,
+        crate::serialize_params(&params),
+        TokenAmount::from_atto(0),
+    ) {
+        err.abort();
+    }
+}
+
+/// FRC-46 receiver hook, called by the datacap actor when DataCap is
+/// transferred to this actor. Credits the sender's balance.
+pub fn receive(params: UniversalReceiverParams) {
+    let caller = rt().caller();
+    if caller != DATACAP_ACTOR || params.type_ != FRC46_TOKEN_TYPE {
+        abort!(
+            USR_FORBIDDEN,
+            "only accepts DataCap, got receiver type {} from f0{}",
+            params.type_,
+            caller
+        );
+    }
+    let received: FRC46TokenReceived = match params.payload.deserialize() {
+        Ok(received) => received,
+        Err(err) => abort!(USR_SERIALIZATION, "failed to parse payload: {}", err),
+    };
+
+    let mut state = State::load();
+    let balance = state.datacap_balance(received.from);
+    state.set_datacap_balance(received.from, balance + received.amount.clone());
+    state.record_event(EventPayload::DatacapReceived(
+        received.from,
+        received.amount,
+    ));
+    state.save();
+}
+
+/// Method num 46.
+/// Allocates the caller's DataCap to pieces stored by managed providers,
+/// through the verified registry.
+pub fn allocate_datacap(params: AllocateParams) {
+    for allocation in &params.allocations {
+        transfer::managed_miner(&Address::new_id(allocation.provider));
+    }
+    let amount = params
+        .allocations
+        .iter()
+        .fold(TokenAmount::from_atto(0), |total, allocation| {
+            total + datacap_for(allocation.size)
+        });
+
+    let depositor = rt().caller();
+    let mut state = State::load();
+    state.validate_not_paused();
+    state.debit_datacap(depositor, &amount);
+    state.record_event(EventPayload::DatacapAllocated(depositor, amount.clone()));
+
+    let requests = AllocationRequests {
+        allocations: params.allocations,
+        extensions: Vec::new(),
+    };
+    send_datacap(
+        &mut state,
+        Address::new_id(VERIFIED_REGISTRY_ACTOR),
+        amount,
+        crate::serialize_params(&requests),
+    );
+    state.save();
+}
+
+/// Method num 47.
+/// Transfers DataCap held for the caller to another address.
+pub fn transfer_datacap(params: TransferDatacapParams) {
+    let depositor = rt().caller();
+    let mut state = State::load();
+    state.validate_not_paused();
+    state.debit_datacap(depositor, &params.amount);
+    state.record_event(EventPayload::DatacapTransferred(
+        depositor,
+        params.to,
+        params.amount.clone(),
+    ));
+
+    send_datacap(&mut state, params.to, params.amount, RawBytes::default());
+    state.save();
+}
+
+/// Method num 48.
+/// Returns the DataCap held on behalf of a depositor.
+pub fn get_datacap_balance(params: DatacapBalanceParams) -> TokenAmount {
+    State::load().datacap_balance(params.depositor)
+}
+
+#[cfg(test)]
+mod test {
+    #[test]
+    fn datacap_covers_one_token_per_byte() {
+        use fvm_shared::econ::TokenAmount;
+        use fvm_shared::piece::PaddedPieceSize;
+
+        assert_eq!(
+            super::datacap_for(PaddedPieceSize(2048)),
+            TokenAmount::from_atto(2048u128 * 1_000_000_000_000_000_000)
+        );
+    }
+
+    #[test]
+    fn datacap_ledger_is_debited() {
+        use fvm_shared::econ::TokenAmount;
+
+        let mut state = crate::State::new(1);
+        let balances = state.datacap_balances;
+        state.set_datacap_balance(10, TokenAmount::from_atto(2048));
+        state.debit_datacap(10, &TokenAmount::from_atto(48));
+        assert_eq!(state.datacap_balance(10), TokenAmount::from_atto(2000));
+        // Spending everything removes the entry again.
+        state.debit_datacap(10, &TokenAmount::from_atto(2000));
+        assert_eq!(state.datacap_balances, balances);
+    }
+}
@@ -0,0 +1,275 @@
+use anyhow::{anyhow, Result};
+use cid::Cid;
+use fvm_ipld_amt::Amt;
+use fvm_ipld_blockstore::Blockstore;
+use fvm_ipld_encoding::tuple::{Deserialize_tuple, Serialize_tuple};
+use fvm_shared::address::Address;
+use fvm_shared::clock::ChainEpoch;
+use fvm_shared::econ::TokenAmount;
+use fvm_shared::{ActorID, MethodNum};
+
+/// Maximum number of events returned by a single `get_events` call.
+pub const MAX_EVENTS_PER_QUERY: u64 = 100;
+
+tuple_enum! {
+    /// A state change, as recorded in the event log.
+    #[derive(Clone, Debug, PartialEq)]
+    pub enum EventPayload {
+        /// `say_hello` bumped the counter to this value.
+        SayHello(count: u64) = 0,
+        /// Funds were sent out of the actor: (to, amount).
+        Withdraw(to: Address, amount: TokenAmount) = 1,
+        /// Funds were sent to the power actor.
+        FundPower(amount: TokenAmount) = 2,
+        /// A miner was created: (id address, robust address).
+        MinerCreated(id_address: Address, robust_address: Address) = 3,
+        /// A miner owner change was confirmed: (miner, new owner).
+        OwnerChanged(miner: Address, owner: Address) = 4,
+        /// A miner worker change was requested: (miner, new worker).
+        WorkerChanged(miner: Address, worker: Address) = 5,
+        /// Funds were withdrawn from a miner: (miner, requested, withdrawn).
+        MinerWithdrawn(miner: Address, requested: TokenAmount, withdrawn: TokenAmount) = 6,
+        /// The admin invoked a miner method: (miner, method, value).
+        MinerCalled(miner: Address, method: MethodNum, value: TokenAmount) = 7,
+        /// A miner method was added to or removed from the allow-list.
+        MinerMethodAllowed(method: MethodNum, allowed: bool) = 8,
+        /// A sensitive operation was proposed, with this transaction ID.
+        TxProposed(tx_id: u64) = 9,
+        /// A signer approved a pending transaction.
+        TxApproved(tx_id: u64) = 10,
+        /// The proposer cancelled a pending transaction.
+        TxCancelled(tx_id: u64) = 11,
+        /// The signers added this signer.
+        SignerAdded(signer: ActorID) = 12,
+        /// The signers removed this signer.
+        SignerRemoved(signer: ActorID) = 13,
+        /// The signers changed the number of approvals operations need.
+        ThresholdChanged(threshold: u64) = 14,
+        /// An admin action was queued: (ID, earliest execution epoch).
+        ActionQueued(id: u64, eta: ChainEpoch) = 15,
+        /// The admin cancelled a queued action.
+        ActionCancelled(id: u64) = 16,
+        /// A queued action was executed.
+        ActionExecuted(id: u64) = 17,
+        /// A queued action changed the account receiving the balance on
+        /// destruct.
+        BeneficiaryChanged(beneficiary: Address) = 18,
+        /// A queued action changed the timelock delay, in epochs.
+        DelayChanged(delay: ChainEpoch) = 19,
+        /// A queued action changed the withdrawal rate limits, which the state
+        /// holds.
+        RateLimitsChanged = 20,
+        /// A queued action changed the account allowed to pause the actor.
+        GuardianChanged(guardian: ActorID) = 21,
+        /// The guardian paused (`true`) or unpaused (`false`) the actor.
+        PausedChanged(paused: bool) = 22,
+        /// Funds were transferred: (to, method, amount).
+        Transferred(to: Address, method: MethodNum, amount: TokenAmount) = 23,
+        /// The admin added to a miner's available balance: (miner, amount).
+        MinerFunded(miner: Address, amount: TokenAmount) = 24,
+        /// The admin added pledge collateral for a miner: (miner, amount).
+        PledgeAdded(miner: Address, amount: TokenAmount) = 25,
+        /// An actor code was added to or removed from the `transfer` allow-list.
+        TransferCodeAllowed(code: Cid, allowed: bool) = 26,
+        /// The admin had a miner repay its fee debt: (miner, amount sent along).
+        DebtRepaid(miner: Address, amount: TokenAmount) = 27,
+        /// Funds were added to a miner's market escrow: (miner, amount).
+        EscrowAdded(miner: Address, amount: TokenAmount) = 28,
+        /// Funds were withdrawn from a miner's market escrow: (miner, requested,
+        /// withdrawn).
+        EscrowWithdrawn(miner: Address, requested: TokenAmount, withdrawn: TokenAmount) = 29,
+        /// A depositor transferred DataCap to the actor: (depositor, amount).
+        DatacapReceived(depositor: ActorID, amount: TokenAmount) = 30,
+        /// A depositor allocated DataCap to managed providers: (depositor,
+        /// amount).
+        DatacapAllocated(depositor: ActorID, amount: TokenAmount) = 31,
+        /// A depositor transferred DataCap out: (depositor, to, amount).
+        DatacapTransferred(depositor: ActorID, to: Address, amount: TokenAmount) = 32,
+        /// A depositor bought pool shares: (depositor, amount paid, shares).
+        SharesMinted(depositor: ActorID, paid: TokenAmount, shares: TokenAmount) = 33,
+        /// Pool shares changed hands: (from, to, shares).
+        SharesTransferred(from: ActorID, to: ActorID, shares: TokenAmount) = 34,
+        /// A holder burnt pool shares: (holder, shares).
+        SharesBurnt(holder: ActorID, shares: TokenAmount) = 35,
+        /// The signers changed the amount `transfer` sends without their
+        /// approval.
+        TransferLimitChanged(limit: TokenAmount) = 36,
+    }
+}
+
+impl EventPayload {
+    /// The name used as the indexed type of the actor event.
+    pub fn name(&self) -> &'static str {
+        match self {
+            EventPayload::SayHello(..) => "SayHello",
+            EventPayload::Withdraw(..) => "Withdraw",
+            EventPayload::FundPower(..) => "FundPower",
+            EventPayload::MinerCreated(..) => "MinerCreated",
+            EventPayload::OwnerChanged(..) => "OwnerChanged",
+            EventPayload::WorkerChanged(..) => "WorkerChanged",
+            EventPayload::MinerWithdrawn(..) => "MinerWithdrawn",
+            EventPayload::MinerCalled(..) => "MinerCalled",
+            EventPayload::MinerMethodAllowed(..) => "MinerMethodAllowed",
+            EventPayload::TxProposed(..) => "TxProposed",
+            EventPayload::TxApproved(..) => "TxApproved",
+            EventPayload::TxCancelled(..) => "TxCancelled",
+            EventPayload::SignerAdded(..) => "SignerAdded",
+            EventPayload::SignerRemoved(..) => "SignerRemoved",
+            EventPayload::ThresholdChanged(..) => "ThresholdChanged",
+            EventPayload::ActionQueued(..) => "ActionQueued",
+            EventPayload::ActionCancelled(..) => "ActionCancelled",
+            EventPayload::ActionExecuted(..) => "ActionExecuted",
+            EventPayload::BeneficiaryChanged(..) => "BeneficiaryChanged",
+            EventPayload::DelayChanged(..) => "DelayChanged",
+            EventPayload::RateLimitsChanged => "RateLimitsChanged",
+            EventPayload::GuardianChanged(..) => "GuardianChanged",
+            EventPayload::PausedChanged(..) => "PausedChanged",
+            EventPayload::Transferred(..) => "Transferred",
+            EventPayload::MinerFunded(..) => "MinerFunded",
+            EventPayload::PledgeAdded(..) => "PledgeAdded",
+            EventPayload::TransferCodeAllowed(..) => "TransferCodeAllowed",
+            EventPayload::DebtRepaid(..) => "DebtRepaid",
+            EventPayload::EscrowAdded(..) => "EscrowAdded",
+            EventPayload::EscrowWithdrawn(..) => "EscrowWithdrawn",
+            EventPayload::DatacapReceived(..) => "DatacapReceived",
+            EventPayload::DatacapAllocated(..) => "DatacapAllocated",
+            EventPayload::DatacapTransferred(..) => "DatacapTransferred",
+            EventPayload::SharesMinted(..) => "SharesMinted",
+            EventPayload::SharesTransferred(..) => "SharesTransferred",
+            EventPayload::SharesBurnt(..) => "SharesBurnt",
+            EventPayload::TransferLimitChanged(..) => "TransferLimitChanged",
+        }
+    }
+}
+
+/// An entry of the event log.
+#[derive(Serialize_tuple, Deserialize_tuple, Clone, Debug, PartialEq)]
+pub struct Event {
+    pub epoch: ChainEpoch,
+    pub caller: ActorID,
+    pub payload: EventPayload,
+}
+
+#[derive(Debug, Serialize_tuple, Deserialize_tuple)]
+pub struct GetEventsParams {
+    /// Index of the first event to return.
+    pub start: u64,
+    /// Maximum number of events to return, capped at `MAX_EVENTS_PER_QUERY`.
+    pub limit: u64,
+}
+
+#[derive(Debug, Serialize_tuple, Deserialize_tuple)]
+pub struct GetEventsReturn {
+    /// Total number of events in the log.
+    pub count: u64,
+    pub events: Vec<Event>,
+}
+
+/// Creates an empty event log and returns its root.
+pub fn empty_log(store: impl Blockstore) -> Result<Cid> {
+    Amt::<Event, _>::new(store)
+        .flush()
+        .map_err(|e| anyhow!("failed to create event log: {}", e))
+}
+
+/// Appends `event` to the log rooted at `root` and returns the new root.
+pub fn append(store: impl Blockstore, root: &Cid, event: Event) -> Result<Cid> {
+    let mut log = Amt::<Event, _>::load(root, store)
+        .map_err(|e| anyhow!("failed to load event log: {}", e))?;
+    let index = log.count();
+    log.set(index, event)
+        .map_err(|e| anyhow!("failed to append event {}: {}", index, e))?;
+    log.flush()
+        .map_err(|e| anyhow!("failed to flush event log: {}", e))
+}
+
+/// Reads up to `limit` events starting at index `start`.
+pub fn read(store: impl Blockstore, root: &Cid, start: u64, limit: u64) -> Result<GetEventsReturn> {
+    let log = Amt::<Event, _>::load(root, store)
+        .map_err(|e| anyhow!("failed to load event log: {}", e))?;
+    let count = log.count();
+    let end = start
+        .saturating_add(limit.min(MAX_EVENTS_PER_QUERY))
+        .min(count);
+
+    let mut events = Vec::new();
+    for index in start..end {
+        match log.get(index) {
+            Ok(Some(event)) => events.push(event.clone()),
+            Ok(None) => return Err(anyhow!("event {} missing from log", index)),
+            Err(e) => return Err(anyhow!("failed to read event {}: {}", index, e)),
+        }
+    }
+    Ok(GetEventsReturn { count, events })
+}
+
+/// Emits `event` through the actor events syscall, so that it shows up in the
+/// message receipt for indexers that follow the chain.
+#[cfg(feature = "actor-events")]
+pub fn emit(event: &Event) -> Result<()> {
+    use fvm_ipld_encoding::RawBytes;
+    use fvm_shared::event::{ActorEvent, Entry, Flags};
+
+    let payload =
+        RawBytes::serialize(event).map_err(|e| anyhow!("failed to serialize event: {}", e))?;
+    let event = ActorEvent {
+        entries: vec![
+            Entry {
+                flags: Flags::FLAG_INDEXED_ALL,
+                key: "type".to_owned(),
+                value: RawBytes::serialize(event.payload.name())
+                    .map_err(|e| anyhow!("failed to serialize event type: {}", e))?,
+            },
+            Entry {
+                flags: Flags::FLAG_INDEXED_KEY,
+                key: "event".to_owned(),
+                value: payload,
+            },
+        ],
+    };
+    fvm_sdk::event::emit_event(&event).map_err(|e| anyhow!("failed to emit event: {:?}", e))
+}
+
+/// Actor events are not available in this FVM version; the state log is the
+/// only record.
+#[cfg(not(feature = "actor-events"))]
+pub fn emit(_event: &Event) -> Result<()> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    #[test]
+    fn event_log_is_read_in_pages() {
+        use super::{Event, EventPayload, MAX_EVENTS_PER_QUERY};
+        use fvm_ipld_blockstore::MemoryBlockstore;
+
+        let bs = MemoryBlockstore::new();
+        let mut root = super::empty_log(&bs).unwrap();
+        for count in 0..MAX_EVENTS_PER_QUERY + 5 {
+            let event = Event {
+                epoch: count as i64,
+                caller: 100,
+                payload: EventPayload::SayHello(count),
+            };
+            root = super::append(&bs, &root, event).unwrap();
+        }
+
+        let page = super::read(&bs, &root, 3, 2).unwrap();
+        assert_eq!(page.count, MAX_EVENTS_PER_QUERY + 5);
+        assert_eq!(page.events.len(), 2);
+        assert_eq!(page.events[0].payload, EventPayload::SayHello(3));
+        // Pages are capped, and stop at the end of the log.
+        assert_eq!(
+            super::read(&bs, &root, 0, u64::MAX).unwrap().events.len() as u64,
+            MAX_EVENTS_PER_QUERY
+        );
+        assert_eq!(
+            super::read(&bs, &root, MAX_EVENTS_PER_QUERY, 10)
+                .unwrap()
+                .events
+                .len(),
+            5
+        );
+    }
+}
@@ -0,0 +1,36 @@
+//! FRC-0042 method numbers. `dispatch` and `abi::METHODS` take the numbers of
+//! the exported methods from the method table in `methods.rs`.
+
+use fvm_shared::MethodNum;
+
+/// Method numbers below this are reserved for builtin and legacy methods.
+pub const FIRST_HASHED_METHOD: MethodNum = 1 << 24;
+
+#[cfg(test)]
+mod test {
+    #[test]
+    fn frc42_method_numbers_match_reference_values() {
+        assert_eq!(crate::datacap::RECEIVER_HOOK, 3726118371);
+        assert_eq!(crate::token::TRANSFER, 80475954);
+        assert_eq!(crate::datacap::FRC46_TOKEN_TYPE, 2233613279);
+    }
+
+    #[test]
+    fn frc42_method_numbers_do_not_collide() {
+        use super::FIRST_HASHED_METHOD;
+        use crate::abi::METHODS;
+
+        for method in METHODS {
+            assert!(method.legacy.iter().all(|n| *n < FIRST_HASHED_METHOD));
+            assert!(method.hashed.iter().all(|n| *n >= FIRST_HASHED_METHOD));
+        }
+        let mut numbers: Vec<u64> = METHODS
+            .iter()
+            .flat_map(|method| method.legacy.into_iter().chain(method.hashed))
+            .collect();
+        let count = numbers.len();
+        numbers.sort_unstable();
+        numbers.dedup();
+        assert_eq!(numbers.len(), count);
+    }
+}
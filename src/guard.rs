@@ -0,0 +1,62 @@
+//! Reentrancy lock around sends. The lock is part of the persisted state, so
+//! an actor called by one of our sends that calls back into this actor sees it
+//! held and can't start another send until the first one returned.
+
+use fvm_ipld_encoding::RawBytes;
+use fvm_shared::address::Address;
+use fvm_shared::econ::TokenAmount;
+use fvm_shared::error::ExitCode;
+use fvm_shared::receipt::Receipt;
+use fvm_shared::MethodNum;
+
+use crate::send::{self, SendError};
+use crate::State;
+
+/// Exit code for sends attempted while another send is in progress.
+pub const ERR_REENTRANT: ExitCode = ExitCode::new(34);
+
+/// The lock was already held.
+#[derive(Debug, PartialEq)]
+pub struct Reentered;
+
+impl State {
+    /// Takes the reentrancy lock, failing if it is already held.
+    pub fn try_lock(&mut self) -> Result<(), Reentered> {
+        if self.locked {
+            return Err(Reentered);
+        }
+        self.locked = true;
+        Ok(())
+    }
+
+    /// Releases the reentrancy lock.
+    pub fn unlock(&mut self) {
+        self.locked = false;
+    }
+}
+
+/// Sends a message with the reentrancy lock held.
+///
+/// `state` must already hold every change the send depends on (checks and
+/// effects before the interaction): it is flushed together with the lock
+/// before the send, without a history entry of its own, then reloaded with the
+/// lock released once the send returned. The caller saves it afterwards. A
+/// failed send leaves the lock to be undone by the caller's abort.
+pub fn send_locked(
+    state: &mut State,
+    to: &Address,
+    method: MethodNum,
+    params: RawBytes,
+    value: TokenAmount,
+) -> Result<Receipt, SendError> {
+    if state.try_lock().is_err() {
+        abort!(ERR_REENTRANT => "send to {} while another send is in progress", to);
+    }
+    state.flush();
+
+    let receipt = send::send(to, method, params, value)?;
+
+    *state = State::load();
+    state.unlock();
+    Ok(receipt)
+}
@@ -0,0 +1,95 @@
+//! Ring of previous state roots. The ring is an AMT referenced from the
+//! actor's `StateRoot` next to the current state rather than from `State`
+//! itself, so that a remembered state doesn't carry the history before it.
+
+use anyhow::{anyhow, Result};
+use cid::Cid;
+use fvm_ipld_amt::Amt;
+use fvm_ipld_blockstore::Blockstore;
+
+use crate::{StateHistoryEntry, STATE_HISTORY_LEN};
+
+/// Creates an empty ring and returns its root.
+pub fn empty(store: impl Blockstore) -> Result<Cid> {
+    Amt::<StateHistoryEntry, _>::new(store)
+        .flush()
+        .map_err(|e| anyhow!("failed to create state history: {}", e))
+}
+
+/// Number of entries remembered once `pushed` entries were pushed.
+pub fn len(pushed: u64) -> u64 {
+    pushed.min(STATE_HISTORY_LEN)
+}
+
+/// Stores `entry` as the `pushed`-th entry of the ring rooted at `root`,
+/// overwriting the oldest one once the ring is full, and returns the new
+/// root.
+pub fn push(
+    store: impl Blockstore,
+    root: &Cid,
+    pushed: u64,
+    entry: StateHistoryEntry,
+) -> Result<Cid> {
+    let mut ring = Amt::<StateHistoryEntry, _>::load(root, store)
+        .map_err(|e| anyhow!("failed to load state history: {}", e))?;
+    ring.set(pushed % STATE_HISTORY_LEN, entry)
+        .map_err(|e| anyhow!("failed to write state history: {}", e))?;
+    ring.flush()
+        .map_err(|e| anyhow!("failed to flush state history: {}", e))
+}
+
+/// Reads the entry pushed `index` entries before the last of `pushed`, 0
+/// being the most recent one, or `None` if the ring doesn't reach that far.
+pub fn get(
+    store: impl Blockstore,
+    root: &Cid,
+    pushed: u64,
+    index: u64,
+) -> Result<Option<StateHistoryEntry>> {
+    if index >= len(pushed) {
+        return Ok(None);
+    }
+    let ring = Amt::<StateHistoryEntry, _>::load(root, store)
+        .map_err(|e| anyhow!("failed to load state history: {}", e))?;
+    let slot = (pushed - 1 - index) % STATE_HISTORY_LEN;
+    match ring.get(slot) {
+        Ok(Some(entry)) => Ok(Some(entry.clone())),
+        Ok(None) => Err(anyhow!("state history slot {} is empty", slot)),
+        Err(e) => Err(anyhow!("failed to read state history: {}", e)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use cid::Cid;
+
+    #[test]
+    fn state_history_is_bounded() {
+        use crate::{StateHistoryEntry, STATE_HISTORY_LEN};
+        use fvm_ipld_blockstore::MemoryBlockstore;
+
+        let root = Cid::try_from("bafy2bzacea6bvgucghtd66eubqazpknwqqpfywtdgp5qxludjsa6tyd6cxwuy")
+            .unwrap();
+        let bs = MemoryBlockstore::new();
+        let mut ring = super::empty(&bs).unwrap();
+        let pushed = STATE_HISTORY_LEN + 4;
+        for epoch in 0..pushed {
+            let entry = StateHistoryEntry {
+                root,
+                epoch: epoch as i64,
+            };
+            ring = super::push(&bs, &ring, epoch, entry).unwrap();
+        }
+
+        assert_eq!(super::len(pushed), STATE_HISTORY_LEN);
+        let epoch = |index| {
+            super::get(&bs, &ring, pushed, index)
+                .unwrap()
+                .map(|e| e.epoch)
+        };
+        assert_eq!(epoch(0), Some(STATE_HISTORY_LEN as i64 + 3));
+        assert_eq!(epoch(STATE_HISTORY_LEN - 1), Some(4));
+        assert_eq!(epoch(STATE_HISTORY_LEN), None);
+        assert_eq!(super::get(&bs, &ring, 2, 2).unwrap(), None);
+    }
+}
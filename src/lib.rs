@@ -1,11 +1,37 @@
+#[macro_use]
+mod macros;
+
+pub mod abi;
 mod blockstore;
+pub mod datacap;
+pub mod events;
+pub mod frc42;
+mod guard;
+mod history;
+mod map;
+pub mod market;
+pub mod miner;
+pub mod multisig;
+pub mod pause;
+pub mod ratelimit;
+pub mod runtime;
+mod send;
+pub mod timelock;
+pub mod token;
+pub mod transfer;
 
 use crate::blockstore::Blockstore;
+use crate::events::{Event, EventPayload, GetEventsParams, GetEventsReturn};
+use crate::multisig::{Operation, ProposeReturn, Transaction};
+use crate::ratelimit::{Bucket, RateLimit};
+use crate::runtime::rt;
+use crate::timelock::QueuedAction;
 use cid::multihash::Code;
 use cid::Cid;
+use fvm_ipld_blockstore::{Blockstore as _, Buffered};
 use fvm_ipld_encoding::strict_bytes;
 use fvm_ipld_encoding::tuple::{Deserialize_tuple, Serialize_tuple};
-use fvm_ipld_encoding::{to_vec, BytesDe, Cbor, CborStore, RawBytes, DAG_CBOR};
+use fvm_ipld_encoding::{BytesDe, Cbor, CborStore, RawBytes, DAG_CBOR};
 use fvm_ipld_hamt::Hamt;
 use fvm_sdk as sdk;
 use fvm_sdk::NO_DATA_BLOCK_ID;
@@ -13,61 +39,376 @@ use fvm_shared::address::{Address, Network};
 use fvm_shared::bigint::bigint_ser;
 use fvm_shared::clock::ChainEpoch;
 use fvm_shared::econ::TokenAmount;
+use fvm_shared::receipt::Receipt;
 use fvm_shared::sector::{RegisteredPoStProof, StoragePower};
 use fvm_shared::smooth::FilterEstimate;
-use fvm_shared::ActorID;
+use fvm_shared::{ActorID, MethodNum};
 use fvm_shared::{HAMT_BIT_WIDTH, METHOD_SEND};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+/// JSON description of the exported methods and their types, generated by
+/// `build.rs` from `abi`.
+pub const ABI_JSON: &str = include_str!(concat!(env!("OUT_DIR"), "/abi.json"));
+
+// `WASM_BINARY`: the compiled actor, built by `build.rs`, for running it in a
+// local FVM from native code.
+#[cfg(not(target_arch = "wasm32"))]
+include!(concat!(env!("OUT_DIR"), "/wasm_binary.rs"));
+
+/// Number of previous states remembered in `StateRoot::history`.
+pub const STATE_HISTORY_LEN: u64 = 16;
+
+/// A previous state root, and the epoch at which it was replaced.
+#[derive(Serialize_tuple, Deserialize_tuple, Clone, Debug, PartialEq)]
+pub struct StateHistoryEntry {
+    pub root: Cid,
+    pub epoch: ChainEpoch,
+}
 
-/// A macro to abort concisely.
-/// This should be part of the SDK as it's very handy.
-macro_rules! abort {
-    ($code:ident, $msg:literal $(, $ex:expr)*) => {
-        fvm_sdk::vm::abort(
-            fvm_shared::error::ExitCode::$code.value(),
-            Some(format!($msg, $($ex,)*).as_str()),
-        )
-    };
+/// The actor's root object: the current state and the ring of the states it
+/// replaced. Only this object knows about the history, so `State` keeps the
+/// same layout in every snapshot.
+///
+/// The first versions of this actor used the `State` as the root. Actor code
+/// can't be upgraded in place, so actors created with those versions keep
+/// running them; use this version by creating a new actor.
+#[derive(Serialize_tuple, Deserialize_tuple, Clone, Debug)]
+pub struct StateRoot {
+    /// The current `State`.
+    pub state: Cid,
+    /// Ring of previous states (AMT of `StateHistoryEntry`).
+    pub history: Cid,
+    /// Number of entries ever pushed to the ring.
+    pub pushed: u64,
+}
+
+impl StateRoot {
+    /// The root of a new actor whose state is `state`, with no history.
+    fn new(state: Cid) -> Self {
+        let history = match history::empty(Blockstore) {
+            Ok(history) => history,
+            Err(err) => abort!(USR_ILLEGAL_STATE, "{}", err),
+        };
+        StateRoot {
+            state,
+            history,
+            pushed: 0,
+        }
+    }
+
+    pub fn load() -> Self {
+        let root = current_root();
+        match Blockstore.get_cbor::<Self>(&root) {
+            Ok(Some(root)) => root,
+            Ok(None) => abort!(USR_ILLEGAL_STATE, "state root does not exist"),
+            // `[count]` is the root of the first versions of this actor.
+            Err(_) if matches!(Blockstore.get_cbor::<(u64,)>(&root), Ok(Some(_))) => abort!(
+                USR_ILLEGAL_STATE,
+                "state root {} is from an earlier version of this actor",
+                root
+            ),
+            Err(err) => abort!(USR_ILLEGAL_STATE, "failed to get state root: {}", err),
+        }
+    }
+
+    /// Stores the root with the blocks it reaches, and makes it the actor's
+    /// root.
+    fn store(&self) -> Cid {
+        let cid = match Blockstore.put_cbor(self, Code::Blake2b256) {
+            Ok(cid) => cid,
+            Err(err) => abort!(USR_SERIALIZATION, "failed to store state root: {}", err),
+        };
+        // Maps and logs changed by this message are still in the blockstore
+        // cache along with the state; write out what the new root reaches.
+        if let Err(err) = Blockstore.flush(&cid) {
+            abort!(USR_ILLEGAL_STATE, "failed to flush state: {}", err);
+        }
+        if let Err(err) = rt().set_root(&cid) {
+            abort!(USR_ILLEGAL_STATE, "failed to set root ciid: {:}", err);
+        }
+        cid
+    }
+
+    /// Records the current state as replaced at `epoch`.
+    pub fn push_history(&mut self, epoch: ChainEpoch) {
+        let entry = StateHistoryEntry {
+            root: self.state,
+            epoch,
+        };
+        self.history = match history::push(Blockstore, &self.history, self.pushed, entry) {
+            Ok(history) => history,
+            Err(err) => abort!(USR_ILLEGAL_STATE, "{}", err),
+        };
+        self.pushed += 1;
+    }
+
+    /// Returns the entry for the state `index` changes ago, 0 being the most
+    /// recently replaced one.
+    pub fn history_entry(&self, index: u64) -> Option<StateHistoryEntry> {
+        match history::get(Blockstore, &self.history, self.pushed, index) {
+            Ok(entry) => entry,
+            Err(err) => abort!(USR_ILLEGAL_STATE, "{}", err),
+        }
+    }
+
+    /// Number of previous states remembered.
+    pub fn history_len(&self) -> u64 {
+        history::len(self.pushed)
+    }
 }
 
 /// The state object.
 #[derive(Serialize_tuple, Deserialize_tuple, Clone, Debug, Default)]
 pub struct State {
     pub count: u64,
+    /// Root of the append-only event log (AMT of `Event`).
+    pub events: Cid,
+    /// The account allowed to administer the actor and its miners.
+    pub admin: ActorID,
+    /// ID addresses of the miners owned by this actor.
+    pub miners: Vec<Address>,
+    /// Miner methods the admin may invoke through `miner_call`.
+    pub miner_methods: Vec<MethodNum>,
+    /// Accounts that approve sensitive operations.
+    pub signers: Vec<ActorID>,
+    /// Number of signer approvals an operation needs to run.
+    pub threshold: u64,
+    /// ID of the next proposed transaction.
+    pub next_tx_id: u64,
+    /// Root of the pending transactions (HAMT of `Transaction` by ID).
+    pub pending: Cid,
+    /// Epochs a queued admin action waits before it may run.
+    pub timelock_delay: ChainEpoch,
+    /// ID of the next queued admin action.
+    pub next_queued_id: u64,
+    /// Root of the queued admin actions (HAMT of `QueuedAction` by ID).
+    pub queued: Cid,
+    /// Account receiving the balance on destruct, the preset account if unset.
+    pub beneficiary: Option<Address>,
+    /// Limit on value leaving the actor across all initiators, if any.
+    pub global_limit: Option<RateLimit>,
+    /// Limit on value leaving the actor per initiator, if any.
+    pub caller_limit: Option<RateLimit>,
+    /// Spending in the current period against `global_limit`.
+    pub global_bucket: Bucket,
+    /// Spending in the current period against `caller_limit` (HAMT of
+    /// `Bucket` by initiator ID).
+    pub caller_buckets: Cid,
+    /// The account allowed to pause and unpause the actor.
+    pub guardian: ActorID,
+    /// Whether value-moving methods are currently blocked.
+    pub paused: bool,
+    /// Held while a send is in progress, see `guard::send_locked`.
+    pub locked: bool,
+    /// Code CIDs of the actors `transfer` may send to.
+    pub transfer_codes: Vec<Cid>,
+    /// Largest amount `transfer` sends without the signers' approval.
+    pub transfer_limit: TokenAmount,
+    /// DataCap held on behalf of depositors (HAMT of `TokenAmount` by
+    /// depositor ID).
+    pub datacap_balances: Cid,
+    /// Pool shares outstanding.
+    pub share_supply: TokenAmount,
+    /// Pool shares of each holder (HAMT of `TokenAmount` by holder ID).
+    pub share_balances: Cid,
+    /// Shares operators may transfer on behalf of holders (HAMT of
+    /// `TokenAmount` by holder and operator ID).
+    pub share_allowances: Cid,
 }
 
 /// We should probably have a derive macro to mark an object as a state object,
 /// and have load and save methods automatically generated for them as part of a
 /// StateObject trait (i.e. impl StateObject for State).
 impl State {
+    /// Creates the initial state, with an empty event log. The admin starts
+    /// out as the only signer and as the guardian.
+    pub fn new(admin: ActorID) -> Self {
+        let events = match events::empty_log(Blockstore) {
+            Ok(events) => events,
+            Err(err) => abort!(USR_ILLEGAL_STATE, "failed to create event log: {}", err),
+        };
+        let pending = match map::empty::<Transaction>(Blockstore) {
+            Ok(pending) => pending,
+            Err(err) => abort!(USR_ILLEGAL_STATE, "{}", err),
+        };
+        let queued = match map::empty::<QueuedAction>(Blockstore) {
+            Ok(queued) => queued,
+            Err(err) => abort!(USR_ILLEGAL_STATE, "{}", err),
+        };
+        let caller_buckets = match map::empty::<Bucket>(Blockstore) {
+            Ok(caller_buckets) => caller_buckets,
+            Err(err) => abort!(USR_ILLEGAL_STATE, "{}", err),
+        };
+        let datacap_balances = match map::empty::<TokenAmount>(Blockstore) {
+            Ok(datacap_balances) => datacap_balances,
+            Err(err) => abort!(USR_ILLEGAL_STATE, "{}", err),
+        };
+        let share_balances = match map::empty::<TokenAmount>(Blockstore) {
+            Ok(share_balances) => share_balances,
+            Err(err) => abort!(USR_ILLEGAL_STATE, "{}", err),
+        };
+        let share_allowances = match map::empty::<TokenAmount>(Blockstore) {
+            Ok(share_allowances) => share_allowances,
+            Err(err) => abort!(USR_ILLEGAL_STATE, "{}", err),
+        };
+        State {
+            events,
+            admin,
+            signers: vec![admin],
+            threshold: 1,
+            pending,
+            timelock_delay: timelock::DEFAULT_TIMELOCK_DELAY,
+            queued,
+            caller_buckets,
+            guardian: admin,
+            datacap_balances,
+            share_balances,
+            share_allowances,
+            ..Default::default()
+        }
+    }
+
     pub fn load() -> Self {
         // First, load the current state root.
-        let root = match sdk::sself::root() {
-            Ok(root) => root,
-            Err(err) => abort!(USR_ILLEGAL_STATE, "failed to get root: {:?}", err),
-        };
+        let root = StateRoot::load();
 
         // Load the actor state from the state tree.
-        match Blockstore.get_cbor::<Self>(&root) {
+        match Blockstore.get_cbor::<Self>(&root.state) {
             Ok(Some(state)) => state,
             Ok(None) => abort!(USR_ILLEGAL_STATE, "state does not exist"),
             Err(err) => abort!(USR_ILLEGAL_STATE, "failed to get state: {}", err),
         }
     }
 
+    /// Persists the state, remembering the replaced one in the history ring.
     pub fn save(&self) -> Cid {
-        let serialized = match to_vec(self) {
-            Ok(s) => s,
-            Err(err) => abort!(USR_SERIALIZATION, "failed to serialize state: {:?}", err),
+        self.write(true)
+    }
+
+    /// Appends an event caused by the current message to the event log, and
+    /// emits it as an actor event where supported.
+    pub fn record_event(&mut self, payload: EventPayload) {
+        let event = Event {
+            epoch: rt().curr_epoch(),
+            caller: rt().caller(),
+            payload,
         };
-        let cid = match sdk::ipld::put(Code::Blake2b256.into(), 32, DAG_CBOR, serialized.as_slice())
-        {
-            Ok(cid) => cid,
-            Err(err) => abort!(USR_SERIALIZATION, "failed to store initial state: {:}", err),
+        self.events = match events::append(Blockstore, &self.events, event.clone()) {
+            Ok(events) => events,
+            Err(err) => abort!(USR_ILLEGAL_STATE, "failed to record event: {}", err),
         };
-        if let Err(err) = sdk::sself::set_root(&cid) {
-            abort!(USR_ILLEGAL_STATE, "failed to set root ciid: {:}", err);
+        if let Err(err) = events::emit(&event) {
+            abort!(USR_ILLEGAL_STATE, "failed to emit event: {}", err);
         }
-        cid
+    }
+
+    /// Aborts unless the current message comes from the admin.
+    pub fn validate_admin(&self) {
+        let caller = rt().caller();
+        if caller != self.admin {
+            abort!(USR_FORBIDDEN, "f0{} is not the admin", caller);
+        }
+    }
+
+    pub fn is_managed_miner(&self, miner: &Address) -> bool {
+        self.miners.contains(miner)
+    }
+
+    /// Adds `miner` (an ID address) to the managed miners, if not there yet.
+    pub fn add_miner(&mut self, miner: Address) {
+        if !self.is_managed_miner(&miner) {
+            self.miners.push(miner);
+        }
+    }
+
+    /// Persists the initial state under a new root. The FVM creates actors
+    /// with the empty array as their root, so the constructor can't load one.
+    pub fn create(&self) -> Cid {
+        StateRoot::new(self.put()).store()
+    }
+
+    /// Persists the state without touching the history ring, for
+    /// intermediate saves within a message, which `save` replaces before the
+    /// message ends.
+    pub fn flush(&self) -> Cid {
+        self.write(false)
+    }
+
+    fn write(&self, remember: bool) -> Cid {
+        let state = self.put();
+        let mut root = StateRoot::load();
+        if remember {
+            root.push_history(rt().curr_epoch());
+        }
+        StateRoot { state, ..root }.store()
+    }
+
+    fn put(&self) -> Cid {
+        match Blockstore.put_cbor(self, Code::Blake2b256) {
+            Ok(cid) => cid,
+            Err(err) => abort!(USR_SERIALIZATION, "failed to store state: {}", err),
+        }
+    }
+}
+
+/// Resolves `addr` to its ID address, aborting if the actor does not exist.
+fn resolve_id_address(addr: &Address) -> Address {
+    match rt().resolve_address(addr) {
+        Some(id) => Address::new_id(id),
+        None => abort!(USR_NOT_FOUND, "actor {} not found", addr),
+    }
+}
+
+/// The bytes of the parameters block `params`.
+pub(crate) fn raw_params(params: u32) -> RawBytes {
+    match rt().params_raw(params) {
+        Ok((_, params)) => RawBytes::new(params),
+        Err(err) => abort!(USR_SERIALIZATION, "failed to read params: {:?}", err),
+    }
+}
+
+/// Decodes the parameters block `params`, aborting with `USR_SERIALIZATION`
+/// if it isn't a `T`.
+pub(crate) fn read_params<T: DeserializeOwned>(params: u32) -> T {
+    match raw_params(params).deserialize() {
+        Ok(params) => params,
+        Err(err) => abort!(USR_SERIALIZATION, "failed to parse params: {}", err),
+    }
+}
+
+/// Encodes the parameters of a send, aborting with `USR_SERIALIZATION` if
+/// they can't be encoded.
+pub(crate) fn serialize_params<T: Serialize>(params: &T) -> RawBytes {
+    match RawBytes::serialize(params) {
+        Ok(params) => params,
+        Err(err) => abort!(USR_SERIALIZATION, "failed to serialize params: {}", err),
+    }
+}
+
+/// Encodes a return value, aborting with `USR_ILLEGAL_STATE` if it can't be
+/// encoded.
+pub(crate) fn serialize_return<T: Serialize>(value: &T) -> RawBytes {
+    match RawBytes::serialize(value) {
+        Ok(ret) => ret,
+        Err(err) => abort!(
+            USR_ILLEGAL_STATE,
+            "failed to serialize return value: {:?}",
+            err
+        ),
+    }
+}
+
+/// Encodes the return value of a method.
+pub(crate) fn return_value<T: Serialize>(value: &T) -> Option<RawBytes> {
+    Some(serialize_return(value))
+}
+
+/// The CID of the actor's state root.
+fn current_root() -> Cid {
+    match rt().root() {
+        Ok(root) => root,
+        Err(err) => abort!(USR_ILLEGAL_STATE, "failed to get root: {:?}", err),
     }
 }
 
@@ -80,29 +421,7 @@ impl State {
 /// that handles state serde and dispatch.
 #[no_mangle]
 pub fn invoke(params: u32) -> u32 {
-    // Conduct method dispatch. Handle input parameters and return data.
-    let ret: Option<RawBytes> = match sdk::message::method_number() {
-        1 => constructor(),
-        2 => say_hello(),
-        3 => get_state_cid(),
-        4 => echo_raw_bytes(params),
-        5 => get_state_cid_cbor(),
-        6 => echo_cid_params(params),
-        7 => get_old_state(params),
-        8 => get_state_as_bytes(params),
-        9 => get_power_actor_state(params),
-        10 => get_current_balance(),
-        11 => get_power_actor_miners(params),
-        12 => withdraw(params),
-        13 => create_miner(params),
-        14 => fund_t04(params),
-        15 => create_miner_1(params),
-        16 => take_owner(params),
-        17 => destruct(),
-        18 => change_worker(params),
-        19 => withdraw_miner(params),
-        _ => abort!(USR_UNHANDLED_MESSAGE, "unrecognized method"),
-    };
+    let ret = dispatch(params);
 
     // Insert the return data block if necessary, and return the correct
     // block ID.
@@ -115,78 +434,53 @@ pub fn invoke(params: u32) -> u32 {
     }
 }
 
+include!("methods.rs");
+
 /// The constructor populates the initial state.
 ///
 /// Method num 1. This is part of the Filecoin calling convention.
 /// InitActor#Exec will call the constructor on method_num = 1.
-pub fn constructor() -> Option<RawBytes> {
+pub fn constructor() {
     // This constant should be part of the SDK.
     const INIT_ACTOR_ADDR: ActorID = 1;
 
     // Should add SDK sugar to perform ACL checks more succinctly.
     // i.e. the equivalent of the validate_* builtin-actors runtime methods.
     // https://github.com/filecoin-project/builtin-actors/blob/master/actors/runtime/src/runtime/fvm.rs#L110-L146
-    if sdk::message::caller() != INIT_ACTOR_ADDR {
+    if rt().caller() != INIT_ACTOR_ADDR {
         abort!(USR_FORBIDDEN, "constructor invoked by non-init actor");
     }
 
-    let state = State::default();
-    state.save();
-    None
+    // The account that had the actor created becomes its admin.
+    let state = State::new(rt().origin());
+    state.create();
 }
 
 /// Method num 2.
-pub fn say_hello() -> Option<RawBytes> {
+pub fn say_hello() -> String {
     let mut state = State::load();
     state.count += 1;
+    state.record_event(EventPayload::SayHello(state.count));
     state.save();
 
-    let caller = sdk::message::caller();
-    let origin = sdk::message::origin();
-    let receiver = sdk::message::receiver();
+    let caller = rt().caller();
+    let origin = rt().origin();
+    let receiver = rt().receiver();
 
-    let ret = to_vec(
-        format!(
-            "Hello world {caller}/{origin}/{receiver} #{}!",
-            &state.count
-        )
-        .as_str(),
-    );
-    match ret {
-        Ok(ret) => Some(RawBytes::new(ret)),
-        Err(err) => {
-            abort!(
-                USR_ILLEGAL_STATE,
-                "failed to serialize return value: {:?}",
-                err
-            );
-        }
-    }
+    format!(
+        "Hello world {caller}/{origin}/{receiver} #{}!",
+        &state.count
+    )
 }
 
 /// Method num 3.
-pub fn get_state_cid() -> Option<RawBytes> {
-    let state_cid = sdk::sself::root().unwrap();
-    Some(RawBytes::new(state_cid.to_bytes()))
+pub fn get_state_cid() -> RawBytes {
+    RawBytes::new(current_root().to_bytes())
 }
 
 /// Method num 4.
-pub fn echo_raw_bytes(params: u32) -> Option<RawBytes> {
-    let params = sdk::message::params_raw(params).unwrap().1;
-    let params = RawBytes::new(params);
-
-    let ret = to_vec(format!("Params {:?}", params).as_str());
-
-    match ret {
-        Ok(ret) => Some(RawBytes::new(ret)),
-        Err(err) => {
-            abort!(
-                USR_ILLEGAL_STATE,
-                "failed to serialize return value: {:?}",
-                err
-            );
-        }
-    }
+pub fn echo_raw_bytes(params: RawBytes) -> String {
+    format!("Params {:?}", params)
 }
 
 #[derive(Debug, Serialize_tuple, Deserialize_tuple)]
@@ -195,52 +489,82 @@ pub struct CidParams {
 }
 
 /// Method num 5.
-pub fn get_state_cid_cbor() -> Option<RawBytes> {
-    let state_cid = sdk::sself::root().unwrap();
-    let cid_for_cbor = CidParams { cid: state_cid };
-    Some(RawBytes::serialize(cid_for_cbor).unwrap())
+pub fn get_state_cid_cbor() -> CidParams {
+    CidParams {
+        cid: current_root(),
+    }
 }
 
 /// Method num 6.
-pub fn echo_cid_params(params: u32) -> Option<RawBytes> {
-    let params = sdk::message::params_raw(params).unwrap().1;
-    let params = RawBytes::new(params);
-    let params: CidParams = params.deserialize().unwrap();
+pub fn echo_cid_params(params: CidParams) -> String {
+    format!("Params {:?}", params)
+}
 
-    let ret = to_vec(format!("Params {:?}", params).as_str());
+#[derive(Debug, Serialize_tuple, Deserialize_tuple)]
+pub struct StateHistoryParams {
+    /// How many state changes ago, 0 being the most recently replaced state.
+    pub index: u64,
+}
 
-    match ret {
-        Ok(ret) => Some(RawBytes::new(ret)),
-        Err(err) => {
-            abort!(
-                USR_ILLEGAL_STATE,
-                "failed to serialize return value: {:?}",
-                err
-            );
-        }
+#[derive(Debug, Serialize_tuple, Deserialize_tuple)]
+pub struct StateSnapshot {
+    pub root: Cid,
+    pub epoch: ChainEpoch,
+    pub state: State,
+}
+
+/// Looks up the history entry requested by the caller, aborting with
+/// `USR_NOT_FOUND` if the ring does not reach that far back.
+fn requested_history_entry(params: &StateHistoryParams) -> StateHistoryEntry {
+    let root = StateRoot::load();
+    match root.history_entry(params.index) {
+        Some(entry) => entry,
+        None => abort!(
+            USR_NOT_FOUND,
+            "no state {} changes ago, {} remembered",
+            params.index,
+            root.history_len()
+        ),
     }
 }
 
 /// Method num 7.
-pub fn get_old_state(params: u32) -> Option<RawBytes> {
-    let params = sdk::message::params_raw(params).unwrap().1;
-    let params = RawBytes::new(params);
-    let params: CidParams = params.deserialize().unwrap();
-    let old_state_cid = params.cid;
-
-    let old_state = Blockstore.get_cbor::<State>(&old_state_cid).unwrap();
-    Some(RawBytes::serialize(&old_state).unwrap())
+/// Returns the typed state as it was `index` changes ago.
+pub fn get_old_state(params: StateHistoryParams) -> StateSnapshot {
+    let entry = requested_history_entry(&params);
+
+    let state = match Blockstore.get_cbor::<State>(&entry.root) {
+        Ok(Some(state)) => state,
+        Ok(None) => abort!(USR_NOT_FOUND, "state {} not found", entry.root),
+        Err(err) => abort!(
+            USR_NOT_FOUND,
+            "failed to load state {}: {}",
+            entry.root,
+            err
+        ),
+    };
+    StateSnapshot {
+        root: entry.root,
+        epoch: entry.epoch,
+        state,
+    }
 }
 
 /// Method num 8.
-pub fn get_state_as_bytes(params: u32) -> Option<RawBytes> {
-    let params = sdk::message::params_raw(params).unwrap().1;
-    let params = RawBytes::new(params);
-    let params: CidParams = params.deserialize().unwrap();
-    let old_state_cid = params.cid;
-
-    let old_state_vec = sdk::ipld::get(&old_state_cid).unwrap();
-    Some(RawBytes::new(old_state_vec))
+/// Returns the raw state block as it was `index` changes ago.
+pub fn get_state_as_bytes(params: StateHistoryParams) -> RawBytes {
+    let entry = requested_history_entry(&params);
+
+    match Blockstore.get(&entry.root) {
+        Ok(Some(old_state_vec)) => RawBytes::new(old_state_vec),
+        Ok(None) => abort!(USR_NOT_FOUND, "state {} not found", entry.root),
+        Err(err) => abort!(
+            USR_NOT_FOUND,
+            "failed to load state {}: {:?}",
+            entry.root,
+            err
+        ),
+    }
 }
 
 /// Storage power actor state
@@ -272,21 +596,30 @@ pub struct PowerActorState {
     pub proof_validation_batch: Option<Cid>,
 }
 
-/// Method num 9.
-pub fn get_power_actor_state(params: u32) -> Option<RawBytes> {
-    let params = sdk::message::params_raw(params).unwrap().1;
-    let params = RawBytes::new(params);
-    let params: CidParams = params.deserialize().unwrap();
-    let state_cid = params.cid;
+/// Loads the power actor state `state_cid`, aborting with `USR_NOT_FOUND` if
+/// there is no such state.
+fn load_power_state(state_cid: &Cid) -> PowerActorState {
+    match Blockstore.get_cbor::<PowerActorState>(state_cid) {
+        Ok(Some(state)) => state,
+        Ok(None) => abort!(USR_NOT_FOUND, "power state {} not found", state_cid),
+        Err(err) => abort!(
+            USR_NOT_FOUND,
+            "failed to load power state {}: {}",
+            state_cid,
+            err
+        ),
+    }
+}
 
-    let state = Blockstore.get_cbor::<PowerActorState>(&state_cid).unwrap();
-    Some(RawBytes::serialize(&state).unwrap())
+/// Method num 9.
+pub fn get_power_actor_state(params: CidParams) -> PowerActorState {
+    load_power_state(&params.cid)
 }
 
 /// Method num 10.
-pub fn get_current_balance() -> Option<RawBytes> {
-    let balance = sdk::sself::current_balance();
-    Some(RawBytes::serialize(balance.to_string()).unwrap())
+pub fn get_current_balance() -> String {
+    let balance = rt().current_balance();
+    balance.to_string()
 }
 
 #[derive(Debug, Serialize_tuple, Deserialize_tuple, Clone, PartialEq)]
@@ -299,27 +632,33 @@ pub struct Claim {
 }
 
 /// Method num 11.
-pub fn get_power_actor_miners(params: u32) -> Option<RawBytes> {
-    let params = sdk::message::params_raw(params).unwrap().1;
-    let params = RawBytes::new(params);
-    let params: CidParams = params.deserialize().unwrap();
-    let state_cid = params.cid;
-
-    let state = Blockstore
-        .get_cbor::<PowerActorState>(&state_cid)
-        .unwrap()
-        .unwrap();
+pub fn get_power_actor_miners(params: CidParams) -> Vec<Address> {
+    let state = load_power_state(&params.cid);
     let claims =
-        Hamt::<Blockstore, _>::load_with_bit_width(&state.claims, Blockstore, HAMT_BIT_WIDTH)
-            .unwrap();
+        Hamt::<Blockstore, _>::load_with_bit_width(&state.claims, Blockstore, HAMT_BIT_WIDTH);
+    let claims = match claims {
+        Ok(claims) => claims,
+        Err(err) => abort!(
+            USR_NOT_FOUND,
+            "failed to load power claims {}: {}",
+            state.claims,
+            err
+        ),
+    };
     let mut miners = Vec::new();
-    claims
-        .for_each(|k, _: &Claim| {
-            miners.push(Address::from_bytes(&k.0)?);
-            Ok(())
-        })
-        .ok()?;
-    Some(RawBytes::serialize(&miners).unwrap())
+    let iterated = claims.for_each(|k, _: &Claim| {
+        miners.push(Address::from_bytes(&k.0)?);
+        Ok(())
+    });
+    if let Err(err) = iterated {
+        abort!(
+            USR_ILLEGAL_ARGUMENT,
+            "failed to read power claims {}: {}",
+            state.claims,
+            err
+        );
+    }
+    miners
 }
 
 #[derive(Debug, Deserialize_tuple)]
@@ -328,29 +667,32 @@ pub struct WithdrawalParams {
 }
 
 /// Method num 12.
-pub fn withdraw(params: u32) -> Option<RawBytes> {
-    let params = sdk::message::params_raw(params).unwrap().1;
-    let params = RawBytes::new(params);
-    let params: WithdrawalParams = params.deserialize().unwrap();
-    let caller = sdk::message::caller();
+/// Proposes to send `amount` to the caller. Returns a `ProposeReturn`.
+pub fn withdraw(params: WithdrawalParams) -> ProposeReturn {
+    State::load().validate_not_paused();
+    let caller = rt().caller();
     let address = Address::new_id(caller);
-    let send_params = RawBytes::default();
 
-    let _receipt =
-        fvm_sdk::send::send(&address, METHOD_SEND, send_params, params.amount.clone()).unwrap();
+    multisig::propose_op(Operation::Withdraw(address, params.amount))
+}
 
-    let ret = to_vec(format!("Withdraw {:?} => f0{}", params, caller).as_str());
+/// Sends `amount` to `to`, once approved. The amount counts against the rate
+/// limits of `initiator`.
+pub(crate) fn exec_withdraw(initiator: ActorID, to: Address, amount: TokenAmount) -> RawBytes {
+    let send_params = RawBytes::default();
 
-    match ret {
-        Ok(ret) => Some(RawBytes::new(ret)),
-        Err(err) => {
-            abort!(
-                USR_ILLEGAL_STATE,
-                "failed to serialize return value: {:?}",
-                err
-            );
-        }
+    let mut state = State::load();
+    state.validate_not_paused();
+    state.charge_rate_limits(initiator, &amount);
+    state.record_event(EventPayload::Withdraw(to, amount.clone()));
+
+    if let Err(err) = guard::send_locked(&mut state, &to, METHOD_SEND, send_params, amount.clone())
+    {
+        err.abort();
     }
+    state.save();
+
+    serialize_return(&format!("Withdraw {:?} => {}", amount, to))
 }
 
 #[derive(Serialize_tuple, Deserialize_tuple, Clone)]
@@ -374,12 +716,11 @@ impl Cbor for CreateMinerParams {}
 
 /// Method num 13.
 /// Here we use this contract address as owner and worker to create a miner in the hacked FVM
-pub fn create_miner(params: u32) -> Option<RawBytes> {
-    let params = sdk::message::params_raw(params).unwrap().1;
-    let params = RawBytes::new(params);
-    let req: CreateMinerParamsReq = params.deserialize().unwrap();
+pub fn create_miner(req: CreateMinerParamsReq) -> String {
+    let mut state = State::load();
+    state.validate_not_paused();
     // caller: who invoke this contract
-    let my_actor_id = sdk::message::receiver();
+    let my_actor_id = rt().receiver();
     let owner = Address::new_id(my_actor_id);
     let power_actor = Address::new_id(4);
 
@@ -390,73 +731,69 @@ pub fn create_miner(params: u32) -> Option<RawBytes> {
         peer: req.peer,
         multiaddrs: Vec::new(),
     };
-    let send_params = RawBytes::serialize(params).unwrap();
+    let send_params = serialize_params(&params);
 
-    let receipt = fvm_sdk::send::send(&power_actor, 2, send_params, TokenAmount::from_atto(0));
-
-    match receipt {
-        Ok(receipt) => {
-            if !receipt.exit_code.is_success() {
-                abort!(USR_ILLEGAL_STATE, "fail create miner");
-            }
+    let receipt = match guard::send_locked(
+        &mut state,
+        &power_actor,
+        2,
+        send_params,
+        TokenAmount::from_atto(0),
+    ) {
+        Ok(receipt) => receipt,
+        Err(err) => err.abort(),
+    };
 
-            let ret = to_vec(
-                format!(
-                    "Receipt exit_code {}, return_data: {:?}, gas_used: {}",
-                    receipt.exit_code,
-                    // receipt.return_data.deserialize::<String>().unwrap(),
-                    receipt.return_data,
-                    receipt.gas_used,
-                )
-                .as_str(),
-            );
+    let created: PowerCreateMinerReturn = match receipt.return_data.deserialize() {
+        Ok(created) => created,
+        Err(err) => abort!(
+            USR_SERIALIZATION,
+            "failed to parse create miner return: {}",
+            err
+        ),
+    };
+    state.add_miner(created.id_address);
+    state.record_event(EventPayload::MinerCreated(
+        created.id_address,
+        created.robust_address,
+    ));
+    state.save();
 
-            match ret {
-                Ok(ret) => Some(RawBytes::new(ret)),
-                Err(err) => {
-                    abort!(
-                        USR_ILLEGAL_STATE,
-                        "failed to serialize return value: {:?}",
-                        err
-                    );
-                }
-            }
-        }
-        Err(err) => {
-            abort!(USR_ILLEGAL_STATE, "fail create miner: {:?}", err);
-        }
-    }
+    format!(
+        "Receipt exit_code {}, return_data: {:?}, gas_used: {}",
+        receipt.exit_code,
+        // receipt.return_data.deserialize::<String>().unwrap(),
+        receipt.return_data,
+        receipt.gas_used,
+    )
 }
 
 /// Method num 14.
-pub fn fund_t04(params: u32) -> Option<RawBytes> {
-    let params = sdk::message::params_raw(params).unwrap().1;
-    let params = RawBytes::new(params);
-    let params: WithdrawalParams = params.deserialize().unwrap();
+/// Sends funds to the power actor. Only the admin may call this, and the
+/// amount counts against the admin's rate limits.
+pub fn fund_t04(params: WithdrawalParams) -> String {
     let power_actor = Address::new_id(4);
-    let send_params = RawBytes::default();
 
-    let _receipt = fvm_sdk::send::send(
+    transfer::send_value(
         &power_actor,
         METHOD_SEND,
-        send_params,
+        RawBytes::default(),
         params.amount.clone(),
-    )
-    .unwrap();
+        EventPayload::FundPower(params.amount.clone()),
+    );
 
-    let ret = to_vec(format!("Withdraw {:?} => f04", params).as_str());
+    format!("Fund {} => f04", params.amount)
+}
 
-    match ret {
-        Ok(ret) => Some(RawBytes::new(ret)),
-        Err(err) => {
-            abort!(
-                USR_ILLEGAL_STATE,
-                "failed to serialize return value: {:?}",
-                err
-            );
-        }
-    }
+/// Return value of the power actor's CreateMiner method.
+#[derive(Serialize_tuple, Deserialize_tuple, Debug)]
+pub struct PowerCreateMinerReturn {
+    /// Canonical ID-based address for the actor.
+    pub id_address: Address,
+    /// Re-org safe address for created actor.
+    pub robust_address: Address,
 }
+impl Cbor for PowerCreateMinerReturn {}
 
 #[derive(Serialize_tuple, Deserialize_tuple, Debug)]
 pub struct CreateMinerReturn {
@@ -470,10 +807,9 @@ impl Cbor for CreateMinerReturn {}
 
 /// Method num 15.
 /// Here we use an account to create miner, then change the owner to this contact id
-pub fn create_miner_1(params: u32) -> Option<RawBytes> {
-    let params = sdk::message::params_raw(params).unwrap().1;
-    let params = RawBytes::new(params);
-    let req = params.deserialize::<CreateMinerParams>().unwrap();
+pub fn create_miner_1(req: CreateMinerParams) -> CreateMinerReturn {
+    let mut state = State::load();
+    state.validate_not_paused();
 
     // caller: who invoke this contract
     let power_actor = Address::new_id(4);
@@ -485,87 +821,99 @@ pub fn create_miner_1(params: u32) -> Option<RawBytes> {
         peer: req.peer,
         multiaddrs: Vec::new(),
     };
-    let send_params = RawBytes::serialize(params.clone()).unwrap();
+    let send_params = serialize_params(&params);
 
-    let receipt = fvm_sdk::send::send(&power_actor, 2, send_params, TokenAmount::from_atto(0));
-
-    if receipt.is_err() {
-        abort!(
-            USR_ILLEGAL_STATE,
-            "fail create miner: {:?}",
-            receipt.err().unwrap()
-        );
-    }
+    let receipt = match guard::send_locked(
+        &mut state,
+        &power_actor,
+        2,
+        send_params,
+        TokenAmount::from_atto(0),
+    ) {
+        Ok(receipt) => receipt,
+        Err(err) => err.abort(),
+    };
 
-    let receipt = receipt.unwrap();
+    let mut ret: CreateMinerReturn = match receipt.return_data.deserialize() {
+        Ok(ret) => ret,
+        Err(err) => abort!(
+            USR_SERIALIZATION,
+            "failed to parse create miner return: {}",
+            err
+        ),
+    };
+    ret.out = params;
 
-    if !receipt.exit_code.is_success() {
-        abort!(
-            USR_ILLEGAL_STATE,
-            "create miner exit_code {:?}",
-            params.clone()
-        );
+    if resolve_id_address(&ret.out.owner) == Address::new_id(rt().receiver()) {
+        state.add_miner(ret.id_address);
     }
+    state.record_event(EventPayload::MinerCreated(
+        ret.id_address,
+        ret.robust_address,
+    ));
+    state.save();
 
-    let mut ret: CreateMinerReturn = RawBytes::deserialize(&receipt.return_data).unwrap();
-    ret.out = params;
-
-    Some(RawBytes::serialize(&ret).unwrap())
+    ret
 }
 
 /// Method num 16.
 /// Owner set owner to me, i call this to approve
-pub fn take_owner(params: u32) -> Option<RawBytes> {
-    let params = sdk::message::params_raw(params).unwrap().1;
-    let params = RawBytes::new(params);
-    let miner_id: Address = params.deserialize().unwrap();
-    let my_actor_id = sdk::message::receiver();
-    let new_owner = Address::new_id(my_actor_id);
+/// Proposes to confirm the owner change. Returns a `ProposeReturn`.
+pub fn take_owner(miner_id: Address) -> ProposeReturn {
+    multisig::propose_op(Operation::TakeOwner(miner_id))
+}
 
-    let send_params = RawBytes::serialize(new_owner).unwrap();
+/// Confirms this actor as the new owner of `miner_id`, once approved.
+pub(crate) fn exec_take_owner(miner_id: Address) -> RawBytes {
+    let my_actor_id = rt().receiver();
+    let new_owner = Address::new_id(my_actor_id);
 
-    let receipt = fvm_sdk::send::send(&miner_id, 23, send_params, TokenAmount::from_atto(0));
+    let send_params = serialize_params(&new_owner);
 
-    if receipt.is_err() {
-        abort!(
-            USR_ILLEGAL_STATE,
-            "fail change owner: {:?}",
-            receipt.err().unwrap()
-        );
+    let mut state = State::load();
+    if let Err(err) = guard::send_locked(
+        &mut state,
+        &miner_id,
+        miner::MINER_CHANGE_OWNER_ADDRESS,
+        send_params,
+        TokenAmount::from_atto(0),
+    ) {
+        err.abort();
     }
 
-    let receipt = receipt.unwrap();
-
-    if !receipt.exit_code.is_success() {
-        abort!(
-            USR_ILLEGAL_STATE,
-            "change owner exit_code {:?}",
-            receipt.exit_code
-        );
-    }
+    state.add_miner(resolve_id_address(&miner_id));
+    state.record_event(EventPayload::OwnerChanged(miner_id, new_owner));
+    state.save();
 
-    let ret = to_vec(format!("ChangeOwner {:?} -> {:?}", miner_id, new_owner).as_str()).unwrap();
-    Some(RawBytes::new(ret))
+    serialize_return(&format!("ChangeOwner {:?} -> {:?}", miner_id, new_owner))
 }
 
 /// Method num 17.
 /// Destruct actor, and transfer balance to preset account
-pub fn destruct() -> Option<RawBytes> {
+/// Proposes the destruction. Returns a `ProposeReturn`.
+pub fn destruct() -> ProposeReturn {
+    State::load().validate_not_paused();
+    multisig::propose_op(Operation::Destruct)
+}
+
+/// Deletes the actor, once approved.
+pub(crate) fn exec_destruct() -> RawBytes {
     let addr_str = "t3sevmeeqqab7t4qoysvmuwxr4jmkx5agyqgazpvxbwlgaqxyz37oiiizqk3dtc5lqjretgzsjnqmpzub2iaia";
 
-    let addr = match Network::Testnet.parse_address(addr_str) {
-        Ok(addr) => addr,
-        Err(err) => {
-            abort!(
-                USR_ILLEGAL_STATE,
-                "destruct actor error: {}",
-                err
-            );
+    let state = State::load();
+    state.validate_not_paused();
+    let addr = match state.beneficiary {
+        Some(addr) => addr,
+        None => match Network::Testnet.parse_address(addr_str) {
+            Ok(addr) => addr,
+            Err(err) => {
+                abort!(USR_ILLEGAL_STATE, "destruct actor error: {}", err);
+            }
         },
     };
 
-    match sdk::sself::self_destruct(&addr) {
-        Ok(_) => Some(RawBytes::default()),
+    match rt().self_destruct(&addr) {
+        Ok(_) => RawBytes::default(),
         Err(err) => {
             abort!(
                 USR_ILLEGAL_STATE,
@@ -577,9 +925,9 @@ pub fn destruct() -> Option<RawBytes> {
 }
 
 #[derive(Serialize_tuple, Deserialize_tuple, Debug)]
-struct ChangeWorkerParamsReq {
-    miner_id: Address,
-    new_worker_id: Address,
+pub struct ChangeWorkerParamsReq {
+    pub miner_id: Address,
+    pub new_worker_id: Address,
 }
 impl Cbor for ChangeWorkerParamsReq{}
 
@@ -592,19 +940,8 @@ impl Cbor for ChangeWorkerAddressParams{}
 
 /// Method num 18.
 /// Change worker address of miner
-pub fn change_worker(params: u32) -> Option<RawBytes> {
-    let params = sdk::message::params_raw(params).unwrap().1;
-    let params = RawBytes::new(params);
-    let params: ChangeWorkerParamsReq = match params.deserialize() {
-        Ok(params) => params,
-        Err(err) => {
-            abort!(
-                USR_ILLEGAL_STATE,
-                "fail parse params: {}",
-                err
-            );
-        },
-    };
+/// Proposes the worker change. Returns a `ProposeReturn`.
+pub fn change_worker(params: ChangeWorkerParamsReq) -> ProposeReturn {
     let miner_id: Address = params.miner_id;
     let new_worker_id: Address;
 
@@ -629,34 +966,36 @@ pub fn change_worker(params: u32) -> Option<RawBytes> {
         },
     };
 
+    multisig::propose_op(Operation::ChangeWorker(miner_id, new_worker_id))
+}
+
+/// Asks `miner_id` to change its worker to `new_worker_id`, once approved.
+pub(crate) fn exec_change_worker(miner_id: Address, new_worker_id: Address) -> RawBytes {
     let params: ChangeWorkerAddressParams = ChangeWorkerAddressParams {
         new_worker: new_worker_id,
         new_control_addresses: Vec::new(),
     };
 
-    let send_params = RawBytes::serialize(params).unwrap();
+    let send_params = serialize_params(&params);
 
-    let receipt = fvm_sdk::send::send(&miner_id, 3, send_params, TokenAmount::from_atto(0));
-    if receipt.is_err() {
-        abort!(
-            USR_ILLEGAL_STATE,
-            "fail change worker: {:?}",
-            receipt.err().unwrap()
-        );
+    let mut state = State::load();
+    if let Err(err) = guard::send_locked(
+        &mut state,
+        &miner_id,
+        miner::MINER_CHANGE_WORKER_ADDRESS,
+        send_params,
+        TokenAmount::from_atto(0),
+    ) {
+        err.abort();
     }
 
-    let receipt = receipt.unwrap();
-
-    if !receipt.exit_code.is_success() {
-        abort!(
-            USR_ILLEGAL_STATE,
-            "change worker exit_code {:?}",
-            receipt.exit_code
-        );
-    }
+    state.record_event(EventPayload::WorkerChanged(miner_id, new_worker_id));
+    state.save();
 
-    let ret = to_vec(format!("ChangeWorker {:?} -> {:?}", miner_id, new_worker_id).as_str()).unwrap();
-    Some(RawBytes::new(ret))
+    serialize_return(&format!(
+        "ChangeWorker {:?} -> {:?}",
+        miner_id, new_worker_id
+    ))
 }
 
 #[derive(Debug, Deserialize_tuple)]
@@ -679,41 +1018,142 @@ pub struct WithdrawBalanceReturn {
 }
 
 /// Method num 19.
-pub fn withdraw_miner(params: u32) -> Option<RawBytes> {
-    let params = sdk::message::params_raw(params).unwrap().1;
-    let params = RawBytes::new(params);
-    let params: WithdrawMinerParams = params.deserialize().unwrap();
-    let miner_id = params.miner_id;
-    let amount = params.amount;
+/// Proposes to withdraw from a miner. Returns a `ProposeReturn`.
+pub fn withdraw_miner(params: WithdrawMinerParams) -> ProposeReturn {
+    State::load().validate_not_paused();
+
+    multisig::propose_op(Operation::WithdrawMiner(params.miner_id, params.amount))
+}
+
+/// Withdraws `amount` from `miner_id`'s available balance, once approved. The
+/// requested amount counts against the rate limits of `initiator`.
+pub(crate) fn exec_withdraw_miner(
+    initiator: ActorID,
+    miner_id: Address,
+    amount: TokenAmount,
+) -> RawBytes {
+    let mut state = State::load();
+    state.validate_not_paused();
+    state.charge_rate_limits(initiator, &amount);
+
     let params = WithdrawBalanceParams {
         amount_requested: amount.clone(),
     };
 
-    let send_params = RawBytes::serialize(params).unwrap();
+    let send_params = serialize_params(&params);
+
+    let receipt = match guard::send_locked(
+        &mut state,
+        &miner_id,
+        miner::MINER_WITHDRAW_BALANCE,
+        send_params,
+        TokenAmount::from_atto(0),
+    ) {
+        Ok(receipt) => receipt,
+        Err(err) => err.abort(),
+    };
+    let withdraw_ret: WithdrawBalanceReturn = match receipt.return_data.deserialize() {
+        Ok(ret) => ret,
+        Err(err) => abort!(
+            USR_SERIALIZATION,
+            "failed to parse withdraw balance return: {}",
+            err
+        ),
+    };
+
+    state.record_event(EventPayload::MinerWithdrawn(
+        miner_id,
+        amount.clone(),
+        withdraw_ret.amount_withdrawn.clone(),
+    ));
+    state.save();
+
+    serialize_return(&format!(
+        "Withdraw request {} => withdrawn {}",
+        amount, withdraw_ret.amount_withdrawn
+    ))
+}
+
+/// Method num 20.
+/// Returns a page of the event log.
+pub fn get_events(params: GetEventsParams) -> GetEventsReturn {
+    let state = State::load();
+    match events::read(Blockstore, &state.events, params.start, params.limit) {
+        Ok(ret) => ret,
+        Err(err) => abort!(USR_ILLEGAL_STATE, "failed to read events: {}", err),
+    }
+}
+
+#[derive(Debug, Serialize_tuple, Deserialize_tuple)]
+pub struct MinerCallParams {
+    pub miner: Address,
+    pub method: MethodNum,
+    pub params: RawBytes,
+    pub value: TokenAmount,
+}
 
-    let receipt =
-        fvm_sdk::send::send(&miner_id, 16, send_params, TokenAmount::from_atto(0)).unwrap();
-    if !receipt.exit_code.is_success() {
+/// Method num 21.
+/// Invokes an allow-listed method on a managed miner, on behalf of the admin.
+/// Methods in `miner::RESTRICTED_MINER_METHODS` are never allowed. Returns the
+/// miner's receipt.
+pub fn miner_call(params: MinerCallParams) -> Receipt {
+    let mut state = State::load();
+    state.validate_admin();
+    state.validate_not_paused();
+    let miner = resolve_id_address(&params.miner);
+    if !state.is_managed_miner(&miner) {
         abort!(
-            USR_ILLEGAL_STATE,
-            "withdraw miner exit_code {:?}",
-            receipt.exit_code
+            USR_FORBIDDEN,
+            "miner {} is not managed by this actor",
+            miner
+        );
+    }
+    // Restricted methods are refused even if they were allow-listed before
+    // the restriction.
+    miner::validate_unrestricted(params.method);
+    if !state.miner_methods.contains(&params.method) {
+        abort!(
+            USR_FORBIDDEN,
+            "miner method {} is not allowed",
+            params.method
         );
     }
-    let withdraw_ret: WithdrawBalanceReturn = RawBytes::deserialize(&receipt.return_data).unwrap();
+    state.charge_rate_limits(state.admin, &params.value);
+
+    let receipt = match guard::send_locked(
+        &mut state,
+        &miner,
+        params.method,
+        params.params,
+        params.value.clone(),
+    ) {
+        Ok(receipt) => receipt,
+        Err(err) => err.abort(),
+    };
 
-    let ret = to_vec(format!("Withdraw request {} => withdrawn {}", amount, withdraw_ret.amount_withdrawn).as_str());
+    state.record_event(EventPayload::MinerCalled(
+        miner,
+        params.method,
+        params.value,
+    ));
+    state.save();
 
-    match ret {
-        Ok(ret) => Some(RawBytes::new(ret)),
-        Err(err) => {
-            abort!(
-                USR_ILLEGAL_STATE,
-                "failed to serialize return value: {:?}",
-                err
-            );
-        }
-    }
+    receipt
+}
+
+#[derive(Debug, Serialize_tuple, Deserialize_tuple)]
+pub struct SetMinerMethodParams {
+    pub method: MethodNum,
+    pub allowed: bool,
+}
+
+/// Method num 22.
+/// Proposes adding a miner method to, or removing it from, the `miner_call`
+/// allow-list. Once approved, the change waits for the timelock delay.
+/// Methods in `miner::RESTRICTED_MINER_METHODS` can't be added. Returns a
+/// `ProposeReturn`.
+pub fn set_miner_method(params: SetMinerMethodParams) -> ProposeReturn {
+    multisig::propose_op(Operation::SetMinerMethod(params.method, params.allowed))
 }
 
 #[cfg(test)]
@@ -722,6 +1162,8 @@ mod test {
     use cid::Cid;
     use fvm_ipld_encoding::RawBytes;
 
+    use super::runtime::{ok_receipt, MockRuntime};
+
     ////////////////// encode
     ///
     // #[test]
@@ -743,6 +1185,757 @@ mod test {
     //     );
     // }
 
+    #[test]
+    fn constructor_is_only_called_by_init() {
+        use fvm_shared::error::ExitCode;
+
+        let rt = MockRuntime::install(100);
+        rt.set_caller(10);
+        let abort = rt
+            .call(1, super::dispatch, RawBytes::default())
+            .unwrap_err();
+        assert_eq!(abort.exit_code, ExitCode::USR_FORBIDDEN);
+
+        let rt = MockRuntime::construct(10);
+        let state = super::State::load();
+        assert_eq!(state.admin, 10);
+        assert_eq!(state.signers, vec![10]);
+        assert_eq!(rt.root.get(), Some(super::rt().root().unwrap()));
+    }
+
+    #[test]
+    fn constructor_replaces_the_empty_array_root() {
+        use super::blockstore::Blockstore;
+        use fvm_ipld_encoding::CborStore;
+
+        // The FVM creates actors with the empty array as their root.
+        let rt = MockRuntime::install(100);
+        let empty = rt.root.get().unwrap();
+        assert_eq!(
+            Blockstore.get_cbor::<Vec<()>>(&empty).unwrap(),
+            Some(vec![])
+        );
+
+        rt.caller.set(1);
+        rt.origin.set(10);
+        rt.call(1, super::dispatch, RawBytes::default()).unwrap();
+        assert_ne!(rt.root.get(), Some(empty));
+        let root = super::StateRoot::load();
+        assert_eq!((root.pushed, root.history_len()), (0, 0));
+        assert_eq!(super::State::load().admin, 10);
+    }
+
+    #[test]
+    fn legacy_count_root_is_rejected() {
+        use super::blockstore::Blockstore;
+        use cid::multihash::Code;
+        use fvm_ipld_encoding::CborStore;
+        use fvm_shared::error::ExitCode;
+
+        // The first versions of this actor used `[count]` as the root.
+        let rt = MockRuntime::install(100);
+        let legacy = Blockstore.put_cbor(&(3u64,), Code::Blake2b256).unwrap();
+        rt.root.set(Some(legacy));
+        rt.set_caller(10);
+
+        let abort = rt
+            .call(2, super::dispatch, RawBytes::default())
+            .unwrap_err();
+        assert_eq!(abort.exit_code, ExitCode::USR_ILLEGAL_STATE);
+        assert!(
+            abort.message.contains("earlier version"),
+            "{}",
+            abort.message
+        );
+        assert_eq!(rt.root.get(), Some(legacy));
+    }
+
+    /// CBOR array of the encoded `fields`, the encoding of a tuple struct.
+    fn cbor_array(fields: &[Vec<u8>]) -> Vec<u8> {
+        let mut out = match fields.len() {
+            len @ 0..=23 => vec![0x80 | len as u8],
+            len => vec![0x98, len as u8],
+        };
+        for field in fields {
+            out.extend_from_slice(field);
+        }
+        out
+    }
+
+    #[test]
+    fn state_root_layout_is_stable() {
+        use fvm_ipld_encoding::{from_slice, to_vec};
+
+        let state = Cid::try_from("bafy2bzacea6bvgucghtd66eubqazpknwqqpfywtdgp5qxludjsa6tyd6cxwuy")
+            .unwrap();
+        let history =
+            Cid::try_from("bafy2bzacece6ioczclolgntuhvnqseyloiz37gikrwyh2f6vbiwlw7etkll3q")
+                .unwrap();
+        let root = super::StateRoot {
+            state,
+            history,
+            pushed: 7,
+        };
+        let golden = cbor_array(&[
+            to_vec(&state).unwrap(),
+            to_vec(&history).unwrap(),
+            to_vec(&7u64).unwrap(),
+        ]);
+        assert_eq!(to_vec(&root).unwrap(), golden);
+        let decoded: super::StateRoot = from_slice(&golden).unwrap();
+        assert_eq!(
+            (decoded.state, decoded.history, decoded.pushed),
+            (state, history, 7)
+        );
+
+        let entry = super::StateHistoryEntry {
+            root: state,
+            epoch: -2,
+        };
+        let golden = cbor_array(&[to_vec(&state).unwrap(), to_vec(&-2i64).unwrap()]);
+        assert_eq!(to_vec(&entry).unwrap(), golden);
+    }
+
+    #[test]
+    fn state_layout_is_stable() {
+        use fvm_ipld_encoding::to_vec;
+
+        // Snapshots in the history keep this layout, so fields may only be
+        // appended; this lists them in their encoded order.
+        let mut state = super::State::new(10);
+        // Distinct values, so that swapped fields of the same type show.
+        state.count = 1;
+        state.next_tx_id = 2;
+        state.next_queued_id = 3;
+        state.guardian = 11;
+        state.paused = true;
+        let golden = cbor_array(&[
+            to_vec(&state.count).unwrap(),
+            to_vec(&state.events).unwrap(),
+            to_vec(&state.admin).unwrap(),
+            to_vec(&state.miners).unwrap(),
+            to_vec(&state.miner_methods).unwrap(),
+            to_vec(&state.signers).unwrap(),
+            to_vec(&state.threshold).unwrap(),
+            to_vec(&state.next_tx_id).unwrap(),
+            to_vec(&state.pending).unwrap(),
+            to_vec(&state.timelock_delay).unwrap(),
+            to_vec(&state.next_queued_id).unwrap(),
+            to_vec(&state.queued).unwrap(),
+            to_vec(&state.beneficiary).unwrap(),
+            to_vec(&state.global_limit).unwrap(),
+            to_vec(&state.caller_limit).unwrap(),
+            to_vec(&state.global_bucket).unwrap(),
+            to_vec(&state.caller_buckets).unwrap(),
+            to_vec(&state.guardian).unwrap(),
+            to_vec(&state.paused).unwrap(),
+            to_vec(&state.locked).unwrap(),
+            to_vec(&state.transfer_codes).unwrap(),
+            to_vec(&state.transfer_limit).unwrap(),
+            to_vec(&state.datacap_balances).unwrap(),
+            to_vec(&state.share_supply).unwrap(),
+            to_vec(&state.share_balances).unwrap(),
+            to_vec(&state.share_allowances).unwrap(),
+        ]);
+        assert_eq!(to_vec(&state).unwrap(), golden);
+    }
+
+    #[test]
+    fn say_hello_keeps_state_history() {
+        let rt = MockRuntime::construct(10);
+        let first = super::StateRoot::load().state;
+        for _ in 0..2 {
+            rt.call(2, super::dispatch, RawBytes::default()).unwrap();
+        }
+        let ret = rt.call(2, super::dispatch, RawBytes::default()).unwrap();
+        assert_eq!(
+            ret.unwrap().deserialize::<String>().unwrap(),
+            "Hello world 10/10/100 #3!"
+        );
+
+        let root = rt.root.get().unwrap();
+        let ret = rt.call(3, super::dispatch, RawBytes::default()).unwrap();
+        assert_eq!(ret.unwrap().bytes(), root.to_bytes());
+        let ret = rt.call(5, super::dispatch, RawBytes::default()).unwrap();
+        assert_eq!(
+            ret.unwrap().deserialize::<super::CidParams>().unwrap().cid,
+            root
+        );
+
+        // Three changes ago is the constructed state.
+        let index = RawBytes::serialize(super::StateHistoryParams { index: 2 }).unwrap();
+        let ret = rt.call(7, super::dispatch, index.clone()).unwrap();
+        let snapshot: super::StateSnapshot = ret.unwrap().deserialize().unwrap();
+        assert_eq!((snapshot.root, snapshot.state.count), (first, 0));
+        let ret = rt.call(8, super::dispatch, index).unwrap();
+        let state: super::State = ret.unwrap().deserialize().unwrap();
+        assert_eq!(state.count, 0);
+
+        let index = RawBytes::serialize(super::StateHistoryParams { index: 3 }).unwrap();
+        let abort = rt.call(7, super::dispatch, index).unwrap_err();
+        assert_eq!(abort.exit_code, fvm_shared::error::ExitCode::USR_NOT_FOUND);
+
+        // Every call recorded an event.
+        let params = RawBytes::serialize(super::GetEventsParams {
+            start: 0,
+            limit: 10,
+        })
+        .unwrap();
+        let ret = rt.call(20, super::dispatch, params).unwrap();
+        let events: super::events::GetEventsReturn = ret.unwrap().deserialize().unwrap();
+        assert_eq!(events.count, 3);
+        assert_eq!(events.events[2].payload, super::EventPayload::SayHello(3));
+    }
+
+    #[test]
+    fn echo_methods_return_their_params() {
+        let rt = MockRuntime::construct(10);
+        let cid = rt.root.get().unwrap();
+
+        let ret = rt
+            .call(4, super::dispatch, RawBytes::new(vec![1, 2]))
+            .unwrap();
+        assert_eq!(
+            ret.unwrap().deserialize::<String>().unwrap(),
+            format!("Params {:?}", RawBytes::new(vec![1, 2]))
+        );
+        let params = RawBytes::serialize(super::CidParams { cid }).unwrap();
+        let ret = rt.call(6, super::dispatch, params).unwrap();
+        assert_eq!(
+            ret.unwrap().deserialize::<String>().unwrap(),
+            format!("Params {:?}", super::CidParams { cid })
+        );
+    }
+
+    #[test]
+    fn power_actor_state_is_read_from_the_blockstore() {
+        use super::blockstore::Blockstore;
+        use cid::multihash::Code;
+        use fvm_ipld_encoding::CborStore;
+        use fvm_ipld_hamt::{BytesKey, Hamt};
+        use fvm_shared::address::Address;
+        use fvm_shared::sector::RegisteredPoStProof;
+        use fvm_shared::HAMT_BIT_WIDTH;
+
+        let rt = MockRuntime::construct(10);
+        let miner = Address::new_id(1000);
+        let mut claims = Hamt::<_, super::Claim>::new_with_bit_width(Blockstore, HAMT_BIT_WIDTH);
+        let claim = super::Claim {
+            window_post_proof_type: RegisteredPoStProof::StackedDRGWindow2KiBV1,
+            raw_byte_power: 2048.into(),
+            quality_adj_power: 2048.into(),
+        };
+        claims.set(BytesKey(miner.to_bytes()), claim).unwrap();
+        let power = super::PowerActorState {
+            miner_count: 1,
+            claims: claims.flush().unwrap(),
+            ..Default::default()
+        };
+        let cid = Blockstore.put_cbor(&power, Code::Blake2b256).unwrap();
+        let params = RawBytes::serialize(super::CidParams { cid }).unwrap();
+
+        let ret = rt.call(9, super::dispatch, params.clone()).unwrap();
+        let state: super::PowerActorState = ret.unwrap().deserialize().unwrap();
+        assert_eq!(state.miner_count, 1);
+        let ret = rt.call(11, super::dispatch, params).unwrap();
+        assert_eq!(
+            ret.unwrap().deserialize::<Vec<Address>>().unwrap(),
+            vec![miner]
+        );
+    }
+
+    #[test]
+    fn current_balance_is_reported() {
+        use fvm_shared::econ::TokenAmount;
+
+        let rt = MockRuntime::construct(10);
+        rt.set_balance(TokenAmount::from_atto(42));
+        let ret = rt.call(10, super::dispatch, RawBytes::default()).unwrap();
+        assert_eq!(
+            ret.unwrap().deserialize::<String>().unwrap(),
+            TokenAmount::from_atto(42).to_string()
+        );
+    }
+
+    #[test]
+    fn withdrawal_is_sent_to_the_caller() {
+        use super::multisig::ProposeReturn;
+        use fvm_shared::address::Address;
+        use fvm_shared::econ::TokenAmount;
+
+        let rt = MockRuntime::construct(10);
+        rt.set_balance(TokenAmount::from_atto(1000));
+        rt.expect_send(
+            Address::new_id(10),
+            fvm_shared::METHOD_SEND,
+            RawBytes::default(),
+            TokenAmount::from_atto(100),
+            ok_receipt(RawBytes::default()),
+        );
+
+        let params = RawBytes::serialize((TokenAmount::from_atto(100),)).unwrap();
+        let ret = rt.call(12, super::dispatch, params).unwrap();
+        let ret: ProposeReturn = ret.unwrap().deserialize().unwrap();
+        assert!(ret.applied);
+        rt.verify();
+        assert_eq!(*rt.balance.borrow(), TokenAmount::from_atto(900));
+        assert!(!super::State::load().locked);
+        // One history entry for the proposal and one for the withdrawal; the
+        // state flushed with the lock for the send adds none of its own.
+        assert_eq!(super::StateRoot::load().history_len(), 2);
+    }
+
+    #[test]
+    fn withdrawal_reentered_from_the_callee_is_rejected() {
+        use super::guard::ERR_REENTRANT;
+        use super::ratelimit::RateLimit;
+        use fvm_shared::address::Address;
+        use fvm_shared::econ::TokenAmount;
+
+        let rt = MockRuntime::construct(10);
+        rt.set_balance(TokenAmount::from_atto(1000));
+        let mut state = super::State::load();
+        state.global_limit = Some(RateLimit {
+            max_amount: TokenAmount::from_atto(1000),
+            period: 100,
+        });
+        state.save();
+
+        rt.expect_send(
+            Address::new_id(10),
+            fvm_shared::METHOD_SEND,
+            RawBytes::default(),
+            TokenAmount::from_atto(100),
+            ok_receipt(RawBytes::default()),
+        );
+        let callee = rt.clone();
+        rt.on_next_send(move || {
+            // The allowance was charged and the withdrawal recorded before
+            // the send, and the lock is held while the callee runs.
+            let state = super::State::load();
+            assert!(state.locked);
+            assert_eq!(state.global_bucket.spent, TokenAmount::from_atto(100));
+            assert_eq!(*callee.balance.borrow(), TokenAmount::from_atto(900));
+
+            // The recipient calls back to withdraw again.
+            let params = RawBytes::serialize((TokenAmount::from_atto(100),)).unwrap();
+            let abort = callee.call(12, super::dispatch, params).unwrap_err();
+            assert_eq!(abort.exit_code, ERR_REENTRANT);
+        });
+
+        let params = RawBytes::serialize((TokenAmount::from_atto(100),)).unwrap();
+        rt.call(12, super::dispatch, params).unwrap();
+        rt.verify();
+
+        // Only the outer withdrawal left the actor and was charged.
+        let state = super::State::load();
+        assert!(!state.locked);
+        assert_eq!(state.global_bucket.spent, TokenAmount::from_atto(100));
+        assert_eq!(*rt.balance.borrow(), TokenAmount::from_atto(900));
+    }
+
+    #[test]
+    fn withdrawal_is_rejected_while_paused() {
+        use fvm_shared::econ::TokenAmount;
+
+        let rt = MockRuntime::construct(10);
+        let mut state = super::State::load();
+        state.paused = true;
+        state.save();
+
+        let params = RawBytes::serialize((TokenAmount::from_atto(100),)).unwrap();
+        let abort = rt.call(12, super::dispatch, params).unwrap_err();
+        assert_eq!(abort.exit_code, super::pause::ERR_PAUSED);
+        rt.verify();
+    }
+
+    #[test]
+    fn power_actor_is_only_funded_by_the_admin() {
+        use super::ratelimit::{RateLimit, ERR_RATE_LIMITED};
+        use fvm_shared::address::Address;
+        use fvm_shared::econ::TokenAmount;
+        use fvm_shared::error::ExitCode;
+
+        let rt = MockRuntime::construct(10);
+        rt.set_balance(TokenAmount::from_atto(1000));
+        let mut state = super::State::load();
+        state.caller_limit = Some(RateLimit {
+            max_amount: TokenAmount::from_atto(5),
+            period: 10,
+        });
+        state.save();
+        let params = RawBytes::serialize((TokenAmount::from_atto(5),)).unwrap();
+
+        rt.set_caller(11);
+        let abort = rt.call(14, super::dispatch, params.clone()).unwrap_err();
+        assert_eq!(abort.exit_code, ExitCode::USR_FORBIDDEN);
+
+        rt.set_caller(10);
+        rt.expect_send(
+            Address::new_id(4),
+            fvm_shared::METHOD_SEND,
+            RawBytes::default(),
+            TokenAmount::from_atto(5),
+            ok_receipt(RawBytes::default()),
+        );
+        let ret = rt.call(14, super::dispatch, params.clone()).unwrap();
+        assert_eq!(
+            ret.unwrap().deserialize::<String>().unwrap(),
+            format!("Fund {} => f04", TokenAmount::from_atto(5))
+        );
+        rt.verify();
+
+        // The amount counted against the admin's allowance.
+        let abort = rt.call(14, super::dispatch, params).unwrap_err();
+        assert_eq!(abort.exit_code, ERR_RATE_LIMITED);
+        rt.verify();
+    }
+
+    #[test]
+    fn created_miners_are_managed() {
+        use fvm_shared::address::Address;
+        use fvm_shared::econ::TokenAmount;
+        use fvm_shared::sector::RegisteredPoStProof;
+
+        let rt = MockRuntime::construct(10);
+        let proof = RegisteredPoStProof::StackedDRGWindow2KiBV1;
+        let us = Address::new_id(100);
+        let created = super::PowerCreateMinerReturn {
+            id_address: Address::new_id(1000),
+            robust_address: Address::new_actor(b"miner"),
+        };
+        rt.expect_send(
+            Address::new_id(4),
+            2,
+            RawBytes::serialize(super::CreateMinerParams {
+                owner: us,
+                worker: us,
+                window_post_proof_type: proof,
+                peer: vec![1],
+                multiaddrs: vec![],
+            })
+            .unwrap(),
+            TokenAmount::from_atto(0),
+            ok_receipt(RawBytes::serialize(&created).unwrap()),
+        );
+
+        let params = RawBytes::serialize(super::CreateMinerParamsReq {
+            window_post_proof_type: proof,
+            peer: vec![1],
+        })
+        .unwrap();
+        rt.call(13, super::dispatch, params).unwrap();
+        rt.verify();
+        assert!(super::State::load().is_managed_miner(&Address::new_id(1000)));
+
+        // A miner created for another owner is not managed.
+        let out = super::CreateMinerParams {
+            owner: Address::new_id(10),
+            worker: Address::new_id(10),
+            window_post_proof_type: proof,
+            peer: vec![2],
+            multiaddrs: vec![],
+        };
+        let created = super::CreateMinerReturn {
+            id_address: Address::new_id(1001),
+            robust_address: Address::new_actor(b"other miner"),
+            out: out.clone(),
+        };
+        rt.expect_send(
+            Address::new_id(4),
+            2,
+            RawBytes::serialize(&out).unwrap(),
+            TokenAmount::from_atto(0),
+            ok_receipt(RawBytes::serialize(&created).unwrap()),
+        );
+        rt.call(15, super::dispatch, RawBytes::serialize(&out).unwrap())
+            .unwrap();
+        rt.verify();
+        assert!(!super::State::load().is_managed_miner(&Address::new_id(1001)));
+    }
+
+    #[test]
+    fn owner_and_worker_changes_are_sent_to_the_miner() {
+        use fvm_shared::address::Address;
+        use fvm_shared::econ::TokenAmount;
+
+        let rt = MockRuntime::construct(10);
+        let miner = Address::new_id(1000);
+        let worker = Address::new_id(11);
+
+        rt.expect_send(
+            miner,
+            23,
+            RawBytes::serialize(Address::new_id(100)).unwrap(),
+            TokenAmount::from_atto(0),
+            ok_receipt(RawBytes::default()),
+        );
+        rt.call(16, super::dispatch, RawBytes::serialize(miner).unwrap())
+            .unwrap();
+        assert!(super::State::load().is_managed_miner(&miner));
+
+        // Worker changes wait for the timelock before reaching the miner.
+        let params = RawBytes::serialize(super::ChangeWorkerParamsReq {
+            miner_id: miner,
+            new_worker_id: worker,
+        })
+        .unwrap();
+        let ret = rt.call(18, super::dispatch, params).unwrap();
+        let ret: super::multisig::ProposeReturn = ret.unwrap().deserialize().unwrap();
+        let queued: super::timelock::QueueReturn = ret.ret.deserialize().unwrap();
+
+        rt.epoch.set(queued.eta);
+        rt.expect_send(
+            miner,
+            3,
+            RawBytes::serialize(super::ChangeWorkerAddressParams {
+                new_worker: worker,
+                new_control_addresses: vec![],
+            })
+            .unwrap(),
+            TokenAmount::from_atto(0),
+            ok_receipt(RawBytes::default()),
+        );
+        let params =
+            RawBytes::serialize(super::timelock::QueuedIDParams { id: queued.id }).unwrap();
+        rt.call(29, super::dispatch, params).unwrap();
+        rt.verify();
+    }
+
+    #[test]
+    fn miner_withdrawal_reports_the_withdrawn_amount() {
+        use fvm_shared::address::Address;
+        use fvm_shared::econ::TokenAmount;
+
+        let rt = MockRuntime::construct(10);
+        let miner = Address::new_id(1000);
+        rt.expect_send(
+            miner,
+            16,
+            RawBytes::serialize(super::WithdrawBalanceParams {
+                amount_requested: TokenAmount::from_atto(50),
+            })
+            .unwrap(),
+            TokenAmount::from_atto(0),
+            ok_receipt(RawBytes::serialize(TokenAmount::from_atto(40)).unwrap()),
+        );
+
+        let params = RawBytes::serialize((miner, TokenAmount::from_atto(50))).unwrap();
+        let ret = rt.call(19, super::dispatch, params).unwrap();
+        let ret: super::multisig::ProposeReturn = ret.unwrap().deserialize().unwrap();
+        assert_eq!(
+            ret.ret.deserialize::<String>().unwrap(),
+            format!(
+                "Withdraw request {} => withdrawn {}",
+                TokenAmount::from_atto(50),
+                TokenAmount::from_atto(40)
+            )
+        );
+        rt.verify();
+    }
+
+    #[test]
+    fn destruct_pays_out_to_the_preset_beneficiary() {
+        use fvm_shared::address::Address;
+
+        let rt = MockRuntime::construct(10);
+        let mut state = super::State::load();
+        state.beneficiary = Some(Address::new_id(10));
+        state.save();
+
+        rt.call(17, super::dispatch, RawBytes::default()).unwrap();
+        assert_eq!(rt.destructed.get(), Some(Address::new_id(10)));
+    }
+
+    #[test]
+    fn miner_calls_are_restricted_to_allowed_methods_of_managed_miners() {
+        use fvm_shared::address::Address;
+        use fvm_shared::econ::TokenAmount;
+        use fvm_shared::error::ExitCode;
+
+        let rt = MockRuntime::construct(10);
+        let miner = Address::new_id(1000);
+        let params = RawBytes::serialize(super::MinerCallParams {
+            miner,
+            method: 18,
+            params: RawBytes::default(),
+            value: TokenAmount::from_atto(0),
+        })
+        .unwrap();
+
+        let abort = rt.call(21, super::dispatch, params.clone()).unwrap_err();
+        assert_eq!(abort.exit_code, ExitCode::USR_FORBIDDEN);
+
+        let mut state = super::State::load();
+        state.add_miner(miner);
+        state.save();
+        let abort = rt.call(21, super::dispatch, params.clone()).unwrap_err();
+        assert_eq!(abort.exit_code, ExitCode::USR_FORBIDDEN);
+
+        // Allow-listing is proposed to the signers, then waits for the
+        // timelock.
+        let mut state = super::State::load();
+        state.signers.push(11);
+        state.threshold = 2;
+        state.save();
+        let allow = RawBytes::serialize(super::SetMinerMethodParams {
+            method: 18,
+            allowed: true,
+        })
+        .unwrap();
+        rt.set_caller(12);
+        let abort = rt.call(22, super::dispatch, allow.clone()).unwrap_err();
+        assert_eq!(abort.exit_code, ExitCode::USR_FORBIDDEN);
+        rt.set_caller(10);
+        let ret = rt.call(22, super::dispatch, allow).unwrap();
+        let ret: super::multisig::ProposeReturn = ret.unwrap().deserialize().unwrap();
+        assert!(!ret.applied);
+        assert_eq!(super::State::load().next_queued_id, 0);
+
+        rt.set_caller(11);
+        let params =
+            RawBytes::serialize(super::multisig::TxnIDParams { tx_id: ret.tx_id }).unwrap();
+        let ret = rt.call(24, super::dispatch, params).unwrap();
+        let ret: super::multisig::ApproveReturn = ret.unwrap().deserialize().unwrap();
+        let queued: super::timelock::QueueReturn = ret.ret.deserialize().unwrap();
+
+        rt.epoch.set(queued.eta);
+        let params =
+            RawBytes::serialize(super::timelock::QueuedIDParams { id: queued.id }).unwrap();
+        rt.call(29, super::dispatch, params).unwrap();
+        assert_eq!(super::State::load().miner_methods, vec![18]);
+        rt.set_caller(10);
+        rt.expect_send(
+            miner,
+            18,
+            RawBytes::default(),
+            TokenAmount::from_atto(0),
+            ok_receipt(RawBytes::default()),
+        );
+        rt.call(21, super::dispatch, params).unwrap();
+        rt.verify();
+    }
+
+    #[test]
+    fn miner_call_value_counts_against_rate_limits() {
+        use super::ratelimit::{RateLimit, ERR_RATE_LIMITED};
+        use fvm_shared::address::Address;
+        use fvm_shared::econ::TokenAmount;
+
+        let rt = MockRuntime::construct(10);
+        rt.set_balance(TokenAmount::from_atto(1000));
+        let miner = Address::new_id(1000);
+        let mut state = super::State::load();
+        state.add_miner(miner);
+        state.miner_methods.push(18);
+        state.global_limit = Some(RateLimit {
+            max_amount: TokenAmount::from_atto(100),
+            period: 10,
+        });
+        state.save();
+        let call = |value: u64| {
+            RawBytes::serialize(super::MinerCallParams {
+                miner,
+                method: 18,
+                params: RawBytes::default(),
+                value: TokenAmount::from_atto(value),
+            })
+            .unwrap()
+        };
+
+        rt.expect_send(
+            miner,
+            18,
+            RawBytes::default(),
+            TokenAmount::from_atto(60),
+            ok_receipt(RawBytes::default()),
+        );
+        rt.call(21, super::dispatch, call(60)).unwrap();
+        rt.verify();
+        assert_eq!(
+            super::State::load().global_bucket.spent,
+            TokenAmount::from_atto(60)
+        );
+
+        // Nothing is sent once the allowance is used up.
+        let abort = rt.call(21, super::dispatch, call(60)).unwrap_err();
+        assert_eq!(abort.exit_code, ERR_RATE_LIMITED);
+        rt.verify();
+        assert_eq!(*rt.balance.borrow(), TokenAmount::from_atto(940));
+    }
+
+    #[test]
+    fn malformed_params_are_rejected_by_every_method() {
+        use fvm_shared::error::ExitCode;
+
+        let rt = MockRuntime::construct(10);
+        // Empty, not CBOR, truncated, and followed by trailing bytes.
+        let malformed: [&[u8]; 4] = [&[], &[0xff], &[0x82, 0x01], &[0x80, 0x00]];
+        let methods = super::abi::METHODS
+            .iter()
+            .filter(|method| !matches!(method.params, None | Some("raw")));
+        for method in methods {
+            for params in malformed {
+                let abort = rt
+                    .call(
+                        method.number(),
+                        super::dispatch,
+                        RawBytes::new(params.to_vec()),
+                    )
+                    .unwrap_err();
+                assert_eq!(
+                    abort.exit_code,
+                    ExitCode::USR_SERIALIZATION,
+                    "{} with params {:?}",
+                    method.name,
+                    params
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn unknown_methods_are_unhandled() {
+        use fvm_shared::error::ExitCode;
+
+        let rt = MockRuntime::construct(10);
+        let abort = rt
+            .call(1000, super::dispatch, RawBytes::default())
+            .unwrap_err();
+        assert_eq!(abort.exit_code, ExitCode::USR_UNHANDLED_MESSAGE);
+    }
+
+    #[test]
+    fn exported_methods_are_reachable_by_name() {
+        use frc42_dispatch::method_hash;
+        use fvm_shared::error::ExitCode;
+
+        let rt = MockRuntime::construct(10);
+        let ret = rt
+            .call(
+                method_hash!("SayHello"),
+                super::dispatch,
+                RawBytes::default(),
+            )
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            ret.deserialize::<String>().unwrap(),
+            "Hello world 10/10/100 #1!"
+        );
+
+        // The constructor is only reachable by its legacy number.
+        rt.caller.set(1);
+        let abort = rt
+            .call(
+                method_hash!("Constructor"),
+                super::dispatch,
+                RawBytes::default(),
+            )
+            .unwrap_err();
+        assert_eq!(abort.exit_code, ExitCode::USR_UNHANDLED_MESSAGE);
+    }
+
     #[test]
     fn simple() {
         let people = "Rustaceans";
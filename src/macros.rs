@@ -0,0 +1,265 @@
+/// A macro to abort concisely.
+/// This should be part of the SDK as it's very handy.
+///
+/// `abort!(USR_FORBIDDEN, ...)` aborts with a named exit code, while
+/// `abort!(code => ...)` aborts with an `ExitCode` computed at runtime.
+macro_rules! abort {
+    ($code:ident, $msg:literal $(, $ex:expr)*) => {
+        $crate::runtime::rt().abort(
+            fvm_shared::error::ExitCode::$code.value(),
+            Some(format!($msg, $($ex,)*).as_str()),
+        )
+    };
+    ($code:expr => $msg:literal $(, $ex:expr)*) => {
+        $crate::runtime::rt().abort(
+            $code.value(),
+            Some(format!($msg, $($ex,)*).as_str()),
+        )
+    };
+}
+
+/// Logs through the debug syscall when the `debug-log` feature is enabled and
+/// the node has actor debugging turned on. Without the feature the arguments
+/// are not evaluated and nothing ends up in the WASM.
+#[cfg(feature = "debug-log")]
+macro_rules! log {
+    ($($arg:tt)*) => {
+        if fvm_sdk::debug::enabled() {
+            fvm_sdk::debug::log(format!($($arg)*));
+        }
+    };
+}
+
+#[cfg(not(feature = "debug-log"))]
+macro_rules! log {
+    ($($arg:tt)*) => {};
+}
+
+/// Declares an enum that is encoded as an array of the variant index
+/// followed by the variant's fields, e.g. `[0, to, amount]`, rather than as
+/// serde's map from the variant name. Variants list their index, which must
+/// follow the declaration order, and name their fields for the encoding.
+/// New variants go at the end so that stored values keep their meaning.
+macro_rules! tuple_enum {
+    (
+        $(#[$meta:meta])*
+        $vis:vis enum $name:ident {
+            $(
+                $(#[$variant_meta:meta])*
+                $variant:ident $(($($field:ident: $ty:ty),* $(,)?))? = $index:literal
+            ),* $(,)?
+        }
+    ) => {
+        $(#[$meta])*
+        $vis enum $name {
+            $(
+                $(#[$variant_meta])*
+                $variant $(($($ty),*))?,
+            )*
+        }
+
+        impl $name {
+            /// Index and name of every variant, in declaration order.
+            pub const VARIANTS: &'static [(u64, &'static str)] =
+                &[$(($index, stringify!($variant))),*];
+        }
+
+        impl serde::Serialize for $name {
+            fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+            where
+                S: serde::Serializer,
+            {
+                use serde::ser::SerializeTuple;
+
+                match self {
+                    $(
+                        $name::$variant $(($($field),*))? => {
+                            let len = 1 $($(+ { let _ = stringify!($field); 1 })*)?;
+                            let mut tuple = serializer.serialize_tuple(len)?;
+                            tuple.serialize_element(&($index as u64))?;
+                            $($(tuple.serialize_element($field)?;)*)?
+                            tuple.end()
+                        }
+                    )*
+                }
+            }
+        }
+
+        impl<'de> serde::Deserialize<'de> for $name {
+            fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+            where
+                D: serde::Deserializer<'de>,
+            {
+                struct TupleVisitor;
+
+                /// Reads the next element, counting the elements read.
+                fn element<'de, A, T>(seq: &mut A, read: &mut usize) -> Result<T, A::Error>
+                where
+                    A: serde::de::SeqAccess<'de>,
+                    T: serde::Deserialize<'de>,
+                {
+                    match seq.next_element()? {
+                        Some(value) => {
+                            *read += 1;
+                            Ok(value)
+                        }
+                        None => Err(serde::de::Error::invalid_length(
+                            *read,
+                            &concat!(stringify!($name), " fields"),
+                        )),
+                    }
+                }
+
+                impl<'de> serde::de::Visitor<'de> for TupleVisitor {
+                    type Value = $name;
+
+                    fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                        f.write_str(concat!("a ", stringify!($name), " array"))
+                    }
+
+                    fn visit_seq<A>(self, mut seq: A) -> Result<$name, A::Error>
+                    where
+                        A: serde::de::SeqAccess<'de>,
+                    {
+                        let mut read = 0;
+                        let index: u64 = element(&mut seq, &mut read)?;
+                        let value = match index {
+                            $(
+                                $index => $name::$variant $(($(
+                                    element::<A, $ty>(&mut seq, &mut read)?
+                                ),*))?,
+                            )*
+                            index => {
+                                return Err(serde::de::Error::custom(format_args!(
+                                    "unknown {} variant {}",
+                                    stringify!($name),
+                                    index
+                                )))
+                            }
+                        };
+                        if seq.next_element::<serde::de::IgnoredAny>()?.is_some() {
+                            return Err(serde::de::Error::invalid_length(
+                                read + 1,
+                                &concat!(stringify!($name), " fields"),
+                            ));
+                        }
+                        Ok(value)
+                    }
+                }
+
+                deserializer.deserialize_seq(TupleVisitor)
+            }
+        }
+    };
+}
+
+/// Generates `dispatch` from the method table in `methods.rs`. Methods are
+/// matched by their legacy and FRC-0042 numbers, their parameters are decoded
+/// with `read_params` and their return values encoded with `return_value`,
+/// so a handler whose signature doesn't match its entry doesn't compile.
+macro_rules! methods {
+    ($(
+        $legacy:tt $($only:ident)? $name:literal
+            => $($handler:ident)::+ ($($params:tt)*) -> ($($ret:tt)*);
+    )*) => {
+        /// Runs the method of the current message with the parameters block
+        /// `params`, and returns its return value.
+        #[deny(unreachable_patterns)]
+        pub fn dispatch(params: u32) -> Option<RawBytes> {
+            match rt().method_number() {
+                $(
+                    method_number!($legacy $($only)? $name) => method_return!(
+                        ($($ret)*),
+                        method_call!($($handler)::+, ($($params)*), params)
+                    ),
+                )*
+                _ => abort!(USR_UNHANDLED_MESSAGE, "unrecognized method"),
+            }
+        }
+    };
+}
+
+/// The pattern matching the numbers of a method in `methods.rs`.
+macro_rules! method_number {
+    (_ $name:literal) => {
+        frc42_dispatch::method_hash!($name)
+    };
+    ($legacy:literal legacy_only $name:literal) => {
+        $legacy
+    };
+    ($legacy:literal $name:literal) => {
+        $legacy | frc42_dispatch::method_hash!($name)
+    };
+}
+
+/// Calls a handler in `methods.rs` with the parameters block `$params`.
+macro_rules! method_call {
+    ($($handler:ident)::+, (), $params:ident) => {
+        $($handler)::+()
+    };
+    ($($handler:ident)::+, (raw), $params:ident) => {
+        $($handler)::+($crate::raw_params($params))
+    };
+    ($($handler:ident)::+, ($ty:ty), $params:ident) => {
+        $($handler)::+($crate::read_params::<$ty>($params))
+    };
+}
+
+/// Encodes what a handler in `methods.rs` returned.
+macro_rules! method_return {
+    ((), $call:expr) => {{
+        let () = $call;
+        None
+    }};
+    ((raw), $call:expr) => {
+        Some($call)
+    };
+    (($ty:ty), $call:expr) => {
+        $crate::return_value::<$ty>(&$call)
+    };
+}
+
+#[cfg(test)]
+mod test {
+    #[test]
+    fn stored_enums_encode_as_index_tuples() {
+        use fvm_ipld_encoding::{from_slice, to_vec};
+        use fvm_shared::address::Address;
+        use fvm_shared::econ::TokenAmount;
+
+        use crate::events::EventPayload;
+        use crate::multisig::Operation;
+        use crate::timelock::TimelockAction;
+
+        let to = Address::new_id(1001);
+        let amount = TokenAmount::from_atto(5);
+
+        let op = Operation::Withdraw(to, amount.clone());
+        let bytes = to_vec(&op).unwrap();
+        assert_eq!(bytes, to_vec(&(0u64, to, amount.clone())).unwrap());
+        assert_eq!(from_slice::<Operation>(&bytes).unwrap(), op);
+
+        // Variants without fields are a one-element array.
+        let bytes = to_vec(&Operation::Destruct).unwrap();
+        assert_eq!(bytes, to_vec(&(4u64,)).unwrap());
+        assert_eq!(
+            from_slice::<Operation>(&bytes).unwrap(),
+            Operation::Destruct
+        );
+
+        let action = TimelockAction::SetRateLimits(None, None);
+        let bytes = to_vec(&action).unwrap();
+        assert_eq!(bytes, to_vec(&(4u64, (), ())).unwrap());
+        assert_eq!(from_slice::<TimelockAction>(&bytes).unwrap(), action);
+
+        let event = EventPayload::MinerWithdrawn(to, amount.clone(), amount.clone());
+        let bytes = to_vec(&event).unwrap();
+        assert_eq!(bytes, to_vec(&(6u64, to, &amount, &amount)).unwrap());
+        assert_eq!(from_slice::<EventPayload>(&bytes).unwrap(), event);
+
+        // Unknown variants and wrong field counts are rejected.
+        assert!(from_slice::<Operation>(&to_vec(&(9u64,)).unwrap()).is_err());
+        assert!(from_slice::<Operation>(&to_vec(&(0u64, to)).unwrap()).is_err());
+        assert!(from_slice::<Operation>(&to_vec(&(4u64, to)).unwrap()).is_err());
+    }
+}
@@ -0,0 +1,106 @@
+//! Small helpers over HAMTs stored in the actor state, each returning the new
+//! root after a change.
+
+use anyhow::{anyhow, Result};
+use cid::Cid;
+use fvm_ipld_blockstore::Blockstore;
+use fvm_ipld_hamt::{BytesKey, Hamt};
+use fvm_shared::HAMT_BIT_WIDTH;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+/// Key for entries indexed by a sequential ID.
+pub fn u64_key(id: u64) -> BytesKey {
+    BytesKey(id.to_be_bytes().to_vec())
+}
+
+fn load<BS, V>(store: BS, root: &Cid) -> Result<Hamt<BS, V>>
+where
+    BS: Blockstore,
+    V: Serialize + DeserializeOwned + PartialEq,
+{
+    Hamt::load_with_bit_width(root, store, HAMT_BIT_WIDTH)
+        .map_err(|e| anyhow!("failed to load map {}: {}", root, e))
+}
+
+/// Creates an empty map and returns its root.
+pub fn empty<V>(store: impl Blockstore) -> Result<Cid>
+where
+    V: Serialize + DeserializeOwned + PartialEq,
+{
+    Hamt::<_, V>::new_with_bit_width(store, HAMT_BIT_WIDTH)
+        .flush()
+        .map_err(|e| anyhow!("failed to create map: {}", e))
+}
+
+/// Reads the value stored under `key`.
+pub fn get<V>(store: impl Blockstore, root: &Cid, key: &BytesKey) -> Result<Option<V>>
+where
+    V: Serialize + DeserializeOwned + PartialEq + Clone,
+{
+    load::<_, V>(store, root)?
+        .get(key)
+        .map(|v| v.cloned())
+        .map_err(|e| anyhow!("failed to read map {}: {}", root, e))
+}
+
+/// Stores `value` under `key` and returns the new root.
+pub fn set<V>(store: impl Blockstore, root: &Cid, key: BytesKey, value: V) -> Result<Cid>
+where
+    V: Serialize + DeserializeOwned + PartialEq,
+{
+    let mut map = load::<_, V>(store, root)?;
+    map.set(key, value)
+        .map_err(|e| anyhow!("failed to write map {}: {}", root, e))?;
+    map.flush()
+        .map_err(|e| anyhow!("failed to flush map: {}", e))
+}
+
+/// Removes the value stored under `key`, if any, and returns the new root.
+pub fn remove<V>(store: impl Blockstore, root: &Cid, key: &BytesKey) -> Result<Cid>
+where
+    V: Serialize + DeserializeOwned + PartialEq,
+{
+    let mut map = load::<_, V>(store, root)?;
+    map.delete(key)
+        .map_err(|e| anyhow!("failed to delete from map {}: {}", root, e))?;
+    map.flush()
+        .map_err(|e| anyhow!("failed to flush map: {}", e))
+}
+
+/// Key for entries indexed by a pair of IDs, such as (owner, operator).
+pub fn pair_key(a: u64, b: u64) -> BytesKey {
+    let mut key = a.to_be_bytes().to_vec();
+    key.extend_from_slice(&b.to_be_bytes());
+    BytesKey(key)
+}
+
+#[cfg(test)]
+mod test {
+    #[test]
+    fn pending_transactions_round_trip_through_a_map() {
+        use crate::multisig::{Operation, Transaction};
+        use fvm_ipld_blockstore::MemoryBlockstore;
+
+        let bs = MemoryBlockstore::new();
+        let empty = super::empty::<Transaction>(&bs).unwrap();
+        let tx = Transaction {
+            op: Operation::AddSigner(7),
+            proposer: 1,
+            approved: vec![1],
+        };
+
+        let root = super::set(&bs, &empty, super::u64_key(0), tx.clone()).unwrap();
+        assert_eq!(
+            super::get(&bs, &root, &super::u64_key(0)).unwrap(),
+            Some(tx)
+        );
+        assert_eq!(
+            super::get::<Transaction>(&bs, &root, &super::u64_key(1)).unwrap(),
+            None
+        );
+        // Removing the only entry brings back the empty root.
+        let root = super::remove::<Transaction>(&bs, &root, &super::u64_key(0)).unwrap();
+        assert_eq!(root, empty);
+    }
+}
@@ -0,0 +1,218 @@
+//! Market actor escrow of managed miners. Storage providers need escrow in
+//! the market actor to publish deals; funds withdrawn from it go to the
+//! provider's owner, this actor.
+
+use cid::Cid;
+use fvm_ipld_encoding::tuple::{Deserialize_tuple, Serialize_tuple};
+use fvm_ipld_encoding::CborStore;
+use fvm_ipld_hamt::BytesKey;
+use fvm_shared::address::Address;
+use fvm_shared::clock::ChainEpoch;
+use fvm_shared::econ::TokenAmount;
+use fvm_shared::MethodNum;
+
+use crate::blockstore::Blockstore;
+use crate::events::EventPayload;
+use crate::{guard, map, transfer, State};
+
+/// ID of the storage market actor.
+pub const MARKET_ACTOR: u64 = 5;
+
+/// Market actor method numbers.
+pub const MARKET_ADD_BALANCE: MethodNum = 2;
+pub const MARKET_WITHDRAW_BALANCE: MethodNum = 3;
+
+/// Storage market actor state, as of the v10 builtin actors. Every field must
+/// be listed for the state to decode, even those this actor doesn't read.
+#[derive(Default, Serialize_tuple, Deserialize_tuple)]
+pub struct MarketActorState {
+    pub proposals: Cid,
+    pub states: Cid,
+    pub pending_proposals: Cid,
+    /// Total escrow of each client and provider (HAMT of `TokenAmount` by ID
+    /// address).
+    pub escrow_table: Cid,
+    /// Part of the escrow locked in deals (HAMT of `TokenAmount` by ID
+    /// address).
+    pub locked_table: Cid,
+    pub next_id: u64,
+    pub deal_ops_by_epoch: Cid,
+    pub last_cron: ChainEpoch,
+    pub total_client_locked_collateral: TokenAmount,
+    pub total_provider_locked_collateral: TokenAmount,
+    pub total_client_storage_fee: TokenAmount,
+    /// Verified registry allocations of deals not yet activated (HAMT of
+    /// allocation IDs by deal ID).
+    pub pending_deal_allocation_ids: Cid,
+}
+
+#[derive(Debug, Serialize_tuple, Deserialize_tuple)]
+pub struct EscrowParams {
+    pub miner: Address,
+    pub amount: TokenAmount,
+}
+
+#[derive(Serialize_tuple, Deserialize_tuple)]
+pub struct MarketWithdrawBalanceParams {
+    pub provider_or_client: Address,
+    pub amount: TokenAmount,
+}
+
+#[derive(Serialize_tuple, Deserialize_tuple)]
+#[serde(transparent)]
+pub struct MarketWithdrawBalanceReturn {
+    pub amount_withdrawn: TokenAmount,
+}
+
+#[derive(Debug, Serialize_tuple, Deserialize_tuple)]
+pub struct MarketBalanceParams {
+    /// Current state of the market actor.
+    pub market_state: Cid,
+    pub miner: Address,
+}
+
+#[derive(Debug, Serialize_tuple, Deserialize_tuple)]
+pub struct MarketBalance {
+    pub escrow: TokenAmount,
+    pub locked: TokenAmount,
+}
+
+/// Method num 43.
+/// Adds funds from this actor's balance to a managed miner's market escrow.
+/// Only the admin may call this.
+pub fn add_market_balance(params: EscrowParams) {
+    let miner = transfer::managed_miner(&params.miner);
+    let send_params = crate::serialize_params(&miner);
+    let event = EventPayload::EscrowAdded(miner, params.amount.clone());
+    transfer::send_value(
+        &Address::new_id(MARKET_ACTOR),
+        MARKET_ADD_BALANCE,
+        send_params,
+        params.amount,
+        event,
+    );
+}
+
+/// Method num 44.
+/// Withdraws funds from a managed miner's market escrow into this actor. Only
+/// the admin may call this. Returns the amount withdrawn.
+pub fn withdraw_market_balance(params: EscrowParams) -> TokenAmount {
+    let mut state = State::load();
+    state.validate_admin();
+    let miner = transfer::managed_miner(&params.miner);
+
+    let send_params = crate::serialize_params(&MarketWithdrawBalanceParams {
+        provider_or_client: miner,
+        amount: params.amount.clone(),
+    });
+    let receipt = match guard::send_locked(
+        &mut state,
+        &Address::new_id(MARKET_ACTOR),
+        MARKET_WITHDRAW_BALANCE,
+        send_params,
+        TokenAmount::from_atto(0),
+    ) {
+        Ok(receipt) => receipt,
+        Err(err) => err.abort(),
+    };
+    let ret: MarketWithdrawBalanceReturn = match receipt.return_data.deserialize() {
+        Ok(ret) => ret,
+        Err(err) => abort!(
+            USR_SERIALIZATION,
+            "failed to parse market withdraw return: {}",
+            err
+        ),
+    };
+
+    state.record_event(EventPayload::EscrowWithdrawn(
+        miner,
+        params.amount,
+        ret.amount_withdrawn.clone(),
+    ));
+    state.save();
+    ret.amount_withdrawn
+}
+
+/// Method num 45.
+/// Returns a miner's escrow and locked balance in the market actor.
+pub fn get_market_balance(params: MarketBalanceParams) -> MarketBalance {
+    let market = match Blockstore.get_cbor::<MarketActorState>(&params.market_state) {
+        Ok(Some(market)) => market,
+        Ok(None) => abort!(
+            USR_NOT_FOUND,
+            "market state {} not found",
+            params.market_state
+        ),
+        Err(err) => abort!(
+            USR_ILLEGAL_ARGUMENT,
+            "failed to load market state {}: {}",
+            params.market_state,
+            err
+        ),
+    };
+
+    // Balance tables are keyed by ID address.
+    let key = BytesKey(crate::resolve_id_address(&params.miner).to_bytes());
+    let balance = |table: &Cid| match map::get::<TokenAmount>(Blockstore, table, &key) {
+        Ok(amount) => amount.unwrap_or_else(|| TokenAmount::from_atto(0)),
+        Err(err) => abort!(USR_ILLEGAL_STATE, "{}", err),
+    };
+    MarketBalance {
+        escrow: balance(&market.escrow_table),
+        locked: balance(&market.locked_table),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    #[test]
+    fn market_state_decodes_v10_layout() {
+        use super::MarketActorState;
+        use crate::blockstore::Blockstore;
+        use cid::multihash::Code;
+        use fvm_ipld_encoding::{from_slice, to_vec, CborStore};
+        use fvm_shared::econ::TokenAmount;
+
+        let cid = |n: u64| Blockstore.put_cbor(&n, Code::Blake2b256).unwrap();
+        let zero = || TokenAmount::from_atto(0);
+
+        // The market state as the v10 market actor stores it.
+        let v10 = to_vec(&(
+            cid(1),
+            cid(2),
+            cid(3),
+            cid(4),
+            cid(5),
+            6u64,
+            cid(7),
+            8i64,
+            zero(),
+            zero(),
+            zero(),
+            cid(12),
+        ))
+        .unwrap();
+        let state: MarketActorState = from_slice(&v10).unwrap();
+        assert_eq!(state.escrow_table, cid(4));
+        assert_eq!(state.locked_table, cid(5));
+        assert_eq!(state.next_id, 6);
+        assert_eq!(state.pending_deal_allocation_ids, cid(12));
+
+        // Without the allocation IDs, as before v10, it doesn't decode.
+        let v9 = to_vec(&(
+            cid(1),
+            cid(2),
+            cid(3),
+            cid(4),
+            cid(5),
+            6u64,
+            cid(7),
+            8i64,
+            zero(),
+            zero(),
+            zero(),
+        ))
+        .unwrap();
+        assert!(from_slice::<MarketActorState>(&v9).is_err());
+    }
+}
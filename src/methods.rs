@@ -0,0 +1,72 @@
+// The exported methods, included by `lib.rs`, where `methods!` generates
+// `dispatch`, and by `abi.rs`, where it generates `METHODS`. Each entry is
+//
+//     legacy number [legacy_only] "FRC-0042 name" => handler(params) -> (return);
+//
+// with `_` for methods that only have an FRC-0042 number, and `legacy_only`
+// for those only reachable by their legacy number. Parameters and return
+// values are a type, `raw` for bytes that aren't CBOR, or nothing. Types are
+// written as `abi::TYPES` names them, so they resolve from the crate root.
+// Legacy numbers are never reused; 35 was `AddPledge`.
+
+methods! {
+    1 legacy_only "Constructor" => constructor() -> ();
+    2 "SayHello" => say_hello() -> (String);
+    3 "GetStateCid" => get_state_cid() -> (raw);
+    4 "EchoRawBytes" => echo_raw_bytes(raw) -> (String);
+    5 "GetStateCidCbor" => get_state_cid_cbor() -> (CidParams);
+    6 "EchoCidParams" => echo_cid_params(CidParams) -> (String);
+    7 "GetOldState" => get_old_state(StateHistoryParams) -> (StateSnapshot);
+    8 "GetStateAsBytes" => get_state_as_bytes(StateHistoryParams) -> (raw);
+    9 "GetPowerActorState" => get_power_actor_state(CidParams) -> (PowerActorState);
+    10 "GetCurrentBalance" => get_current_balance() -> (String);
+    11 "GetPowerActorMiners" => get_power_actor_miners(CidParams) -> (Vec<Address>);
+    12 "Withdraw" => withdraw(WithdrawalParams) -> (multisig::ProposeReturn);
+    13 "CreateMiner" => create_miner(CreateMinerParamsReq) -> (String);
+    14 "FundPowerActor" => fund_t04(WithdrawalParams) -> (String);
+    15 "CreateMinerWithOwner" => create_miner_1(CreateMinerParams) -> (CreateMinerReturn);
+    16 "TakeOwner" => take_owner(Address) -> (multisig::ProposeReturn);
+    17 "Destruct" => destruct() -> (multisig::ProposeReturn);
+    18 "ChangeWorker" => change_worker(ChangeWorkerParamsReq) -> (multisig::ProposeReturn);
+    19 "WithdrawMiner" => withdraw_miner(WithdrawMinerParams) -> (multisig::ProposeReturn);
+    20 "GetEvents" => get_events(events::GetEventsParams) -> (events::GetEventsReturn);
+    21 "MinerCall" => miner_call(MinerCallParams) -> (Receipt);
+    22 "SetMinerMethod" => set_miner_method(SetMinerMethodParams) -> (multisig::ProposeReturn);
+    23 "Propose" => multisig::propose(multisig::ProposeParams) -> (multisig::ProposeReturn);
+    24 "Approve" => multisig::approve(multisig::TxnIDParams) -> (multisig::ApproveReturn);
+    25 "Cancel" => multisig::cancel(multisig::TxnIDParams) -> ();
+    26 "GetTransaction" => multisig::get_transaction(multisig::TxnIDParams) -> (multisig::Transaction);
+    27 "Queue" => timelock::queue(timelock::QueueParams) -> (timelock::QueueReturn);
+    28 "CancelQueued" => timelock::cancel_queued(timelock::QueuedIDParams) -> ();
+    29 "ExecuteQueued" => timelock::execute_queued(timelock::QueuedIDParams) -> (raw);
+    30 "GetQueued" => timelock::get_queued(timelock::QueuedIDParams) -> (timelock::QueuedAction);
+    31 "GetAllowance" => ratelimit::get_allowance(ratelimit::AllowanceParams) -> (ratelimit::AllowanceReturn);
+    32 "SetPaused" => pause::set_paused(pause::SetPausedParams) -> ();
+    33 "TransferFunds" => transfer::transfer(transfer::TransferParams) -> (Receipt);
+    34 "FundMiner" => transfer::fund_miner(transfer::FundMinerParams) -> ();
+    36 "RepayDebt" => miner::repay_debt(miner::RepayDebtParams) -> ();
+    37 "TopUpPledge" => miner::top_up_pledge(miner::TopUpPledgeParams) -> ();
+    38 "GetMinerFunds" => miner::get_miner_funds(miner::MinerFundsParams) -> (Vec<miner::MinerFunds>);
+    39 "TerminateSectors" => miner::terminate_sectors(miner::TerminateSectorsReq) -> (miner::TerminateSectorsReturn);
+    40 "DeclareFaults" => miner::declare_faults(miner::DeclareFaultsReq) -> ();
+    41 "DeclareFaultsRecovered" => miner::declare_faults_recovered(miner::DeclareFaultsRecoveredReq) -> ();
+    42 "ExtendSectorExpiration" => miner::extend_sector_expiration(miner::ExtendSectorExpirationReq) -> ();
+    43 "AddMarketBalance" => market::add_market_balance(market::EscrowParams) -> ();
+    44 "WithdrawMarketBalance" => market::withdraw_market_balance(market::EscrowParams) -> (TokenAmount);
+    45 "GetMarketBalance" => market::get_market_balance(market::MarketBalanceParams) -> (market::MarketBalance);
+    46 "AllocateDatacap" => datacap::allocate_datacap(datacap::AllocateParams) -> ();
+    47 "TransferDatacap" => datacap::transfer_datacap(datacap::TransferDatacapParams) -> ();
+    48 "GetDatacapBalance" => datacap::get_datacap_balance(datacap::DatacapBalanceParams) -> (TokenAmount);
+    49 "Deposit" => token::deposit() -> (TokenAmount);
+    _ "Receive" => datacap::receive(datacap::UniversalReceiverParams) -> ();
+    _ "Name" => token::name() -> (String);
+    _ "Symbol" => token::symbol() -> (String);
+    _ "TotalSupply" => token::total_supply() -> (TokenAmount);
+    _ "BalanceOf" => token::balance_of(Address) -> (TokenAmount);
+    _ "Transfer" => token::transfer(token::TransferParams) -> (token::TransferReturn);
+    _ "TransferFrom" => token::transfer_from(token::TransferFromParams) -> (token::TransferFromReturn);
+    _ "IncreaseAllowance" => token::increase_allowance(token::IncreaseAllowanceParams) -> (TokenAmount);
+    _ "DecreaseAllowance" => token::decrease_allowance(token::DecreaseAllowanceParams) -> (TokenAmount);
+    _ "RevokeAllowance" => token::revoke_allowance(token::RevokeAllowanceParams) -> (TokenAmount);
+    _ "Burn" => token::burn(token::BurnParams) -> (token::BurnReturn);
+}
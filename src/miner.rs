@@ -0,0 +1,398 @@
+//! Fee debt, pledge and sector management of managed miners. This actor can't
+//! read another actor's state root, so queries take the miner state CIDs from
+//! the caller, the same way `get_power_actor_state` does.
+//!
+//! The sector method parameters mirror the miner types of builtin-actors.
+
+use cid::Cid;
+use fvm_ipld_bitfield::BitField;
+use fvm_ipld_encoding::tuple::{Deserialize_tuple, Serialize_tuple};
+use fvm_ipld_encoding::{CborStore, RawBytes};
+use fvm_shared::address::Address;
+use fvm_shared::clock::ChainEpoch;
+use fvm_shared::econ::TokenAmount;
+use fvm_shared::receipt::Receipt;
+use fvm_shared::{MethodNum, METHOD_SEND};
+use serde::Serialize;
+
+use crate::blockstore::Blockstore;
+use crate::events::EventPayload;
+use crate::guard;
+use crate::transfer;
+use crate::State;
+
+/// Miner actor method numbers.
+pub const MINER_CHANGE_WORKER_ADDRESS: MethodNum = 3;
+pub const MINER_EXTEND_SECTOR_EXPIRATION: MethodNum = 8;
+pub const MINER_TERMINATE_SECTORS: MethodNum = 9;
+pub const MINER_DECLARE_FAULTS: MethodNum = 10;
+pub const MINER_DECLARE_FAULTS_RECOVERED: MethodNum = 11;
+pub const MINER_WITHDRAW_BALANCE: MethodNum = 16;
+pub const MINER_CONFIRM_UPDATE_WORKER_KEY: MethodNum = 21;
+pub const MINER_REPAY_DEBT: MethodNum = 22;
+pub const MINER_CHANGE_OWNER_ADDRESS: MethodNum = 23;
+pub const MINER_CHANGE_BENEFICIARY: MethodNum = 30;
+
+/// Miner methods that move funds or control of the miner. They are only sent
+/// by the methods that need the signers' approval, and can't be allow-listed
+/// for `miner_call`.
+pub const RESTRICTED_MINER_METHODS: [MethodNum; 5] = [
+    MINER_CHANGE_WORKER_ADDRESS,
+    MINER_WITHDRAW_BALANCE,
+    MINER_CONFIRM_UPDATE_WORKER_KEY,
+    MINER_CHANGE_OWNER_ADDRESS,
+    MINER_CHANGE_BENEFICIARY,
+];
+
+/// Aborts if `method` is one of `RESTRICTED_MINER_METHODS`.
+pub(crate) fn validate_unrestricted(method: MethodNum) {
+    if RESTRICTED_MINER_METHODS.contains(&method) {
+        abort!(
+            USR_FORBIDDEN,
+            "miner method {} moves funds or control and needs the signers' approval",
+            method
+        );
+    }
+}
+
+/// Storage miner actor state, as of the v10 builtin actors. Every field must
+/// be listed for the state to decode, even those this actor doesn't read.
+#[derive(Serialize_tuple, Deserialize_tuple, Clone, Debug)]
+pub struct MinerActorState {
+    pub info: Cid,
+    pub pre_commit_deposits: TokenAmount,
+    pub locked_funds: TokenAmount,
+    pub vesting_funds: Cid,
+    pub fee_debt: TokenAmount,
+    pub initial_pledge: TokenAmount,
+    pub pre_committed_sectors: Cid,
+    pub pre_committed_sectors_cleanup: Cid,
+    pub allocated_sectors: Cid,
+    pub sectors: Cid,
+    pub proving_period_start: ChainEpoch,
+    pub current_deadline: u64,
+    pub deadlines: Cid,
+    pub early_terminations: BitField,
+    /// Whether the miner has a deadline cron callback registered with the
+    /// power actor.
+    pub deadline_cron_active: bool,
+}
+
+/// Loads a miner actor state block supplied by the caller.
+fn load_miner_state(cid: &Cid) -> MinerActorState {
+    match Blockstore.get_cbor::<MinerActorState>(cid) {
+        Ok(Some(state)) => state,
+        Ok(None) => abort!(USR_NOT_FOUND, "miner state {} not found", cid),
+        Err(err) => abort!(
+            USR_ILLEGAL_ARGUMENT,
+            "failed to load miner state {}: {}",
+            cid,
+            err
+        ),
+    }
+}
+
+#[derive(Debug, Serialize_tuple, Deserialize_tuple)]
+pub struct RepayDebtParams {
+    pub miner: Address,
+    /// Funds sent along, added to the miner balance before it repays.
+    pub amount: TokenAmount,
+}
+
+#[derive(Debug, Serialize_tuple, Deserialize_tuple)]
+pub struct TopUpPledgeParams {
+    pub miner: Address,
+    /// Current state of the miner, to read its fee debt from.
+    pub miner_state: Cid,
+    /// Funds the miner should have available for pledge after repaying debt.
+    pub amount: TokenAmount,
+}
+
+#[derive(Debug, Serialize_tuple, Deserialize_tuple)]
+pub struct MinerFundsParams {
+    /// States of the managed miners, in the order of `State::miners`.
+    pub miner_states: Vec<Cid>,
+}
+
+#[derive(Debug, Serialize_tuple, Deserialize_tuple)]
+pub struct MinerFunds {
+    pub miner: Address,
+    pub fee_debt: TokenAmount,
+    pub locked_funds: TokenAmount,
+    pub initial_pledge: TokenAmount,
+    pub pre_commit_deposits: TokenAmount,
+}
+
+/// Method num 36.
+/// Has a managed miner repay its fee debt, sending `amount` from this actor's
+/// balance along. Only the admin may call this.
+pub fn repay_debt(params: RepayDebtParams) {
+    transfer::fund_managed_miner(
+        &params.miner,
+        MINER_REPAY_DEBT,
+        params.amount,
+        EventPayload::DebtRepaid,
+    );
+}
+
+/// Method num 37.
+/// Makes `amount` available to a managed miner for upcoming prove-commits.
+/// Outstanding fee debt is sent along and repaid first, since the miner can't
+/// lock pledge while in debt. Only the admin may call this.
+///
+/// The debt is read from the caller-supplied `miner_state`, which can't be
+/// checked against the miner's actual state root. A stale or forged state
+/// only changes how much is sent: funds beyond the debt stay in the managed
+/// miner's balance, which this actor still controls as owner.
+pub fn top_up_pledge(params: TopUpPledgeParams) {
+    let miner_state = load_miner_state(&params.miner_state);
+    let total = params.amount + miner_state.fee_debt.clone();
+    let method = if miner_state.fee_debt > TokenAmount::from_atto(0) {
+        MINER_REPAY_DEBT
+    } else {
+        METHOD_SEND
+    };
+
+    transfer::fund_managed_miner(&params.miner, method, total, EventPayload::PledgeAdded);
+}
+
+/// Method num 38.
+/// Returns the fee debt and locked funds of every managed miner.
+///
+/// The funds are read from the caller-supplied `miner_states` as they are:
+/// this actor can't read the miners' state roots to check them, so the result
+/// is only as accurate as the states the caller passes.
+pub fn get_miner_funds(params: MinerFundsParams) -> Vec<MinerFunds> {
+    let state = State::load();
+    if params.miner_states.len() != state.miners.len() {
+        abort!(
+            USR_ILLEGAL_ARGUMENT,
+            "expected {} miner states, got {}",
+            state.miners.len(),
+            params.miner_states.len()
+        );
+    }
+
+    state
+        .miners
+        .iter()
+        .zip(params.miner_states.iter())
+        .map(|(miner, cid)| {
+            let miner_state = load_miner_state(cid);
+            MinerFunds {
+                miner: *miner,
+                fee_debt: miner_state.fee_debt,
+                locked_funds: miner_state.locked_funds,
+                initial_pledge: miner_state.initial_pledge,
+                pre_commit_deposits: miner_state.pre_commit_deposits,
+            }
+        })
+        .collect()
+}
+
+#[derive(Debug, Serialize_tuple, Deserialize_tuple)]
+pub struct TerminationDeclaration {
+    pub deadline: u64,
+    pub partition: u64,
+    pub sectors: BitField,
+}
+
+#[derive(Debug, Serialize_tuple, Deserialize_tuple)]
+pub struct TerminateSectorsParams {
+    pub terminations: Vec<TerminationDeclaration>,
+}
+
+#[derive(Debug, Serialize_tuple, Deserialize_tuple)]
+pub struct TerminateSectorsReturn {
+    /// Whether all early terminations were processed.
+    pub done: bool,
+}
+
+#[derive(Debug, Serialize_tuple, Deserialize_tuple)]
+pub struct FaultDeclaration {
+    pub deadline: u64,
+    pub partition: u64,
+    pub sectors: BitField,
+}
+
+#[derive(Debug, Serialize_tuple, Deserialize_tuple)]
+pub struct DeclareFaultsParams {
+    pub faults: Vec<FaultDeclaration>,
+}
+
+#[derive(Debug, Serialize_tuple, Deserialize_tuple)]
+pub struct RecoveryDeclaration {
+    pub deadline: u64,
+    pub partition: u64,
+    pub sectors: BitField,
+}
+
+#[derive(Debug, Serialize_tuple, Deserialize_tuple)]
+pub struct DeclareFaultsRecoveredParams {
+    pub recoveries: Vec<RecoveryDeclaration>,
+}
+
+#[derive(Debug, Serialize_tuple, Deserialize_tuple)]
+pub struct ExpirationExtension {
+    pub deadline: u64,
+    pub partition: u64,
+    pub sectors: BitField,
+    pub new_expiration: ChainEpoch,
+}
+
+#[derive(Debug, Serialize_tuple, Deserialize_tuple)]
+pub struct ExtendSectorExpirationParams {
+    pub extensions: Vec<ExpirationExtension>,
+}
+
+#[derive(Debug, Serialize_tuple, Deserialize_tuple)]
+pub struct TerminateSectorsReq {
+    pub miner: Address,
+    pub params: TerminateSectorsParams,
+}
+
+#[derive(Debug, Serialize_tuple, Deserialize_tuple)]
+pub struct DeclareFaultsReq {
+    pub miner: Address,
+    pub params: DeclareFaultsParams,
+}
+
+#[derive(Debug, Serialize_tuple, Deserialize_tuple)]
+pub struct DeclareFaultsRecoveredReq {
+    pub miner: Address,
+    pub params: DeclareFaultsRecoveredParams,
+}
+
+#[derive(Debug, Serialize_tuple, Deserialize_tuple)]
+pub struct ExtendSectorExpirationReq {
+    pub miner: Address,
+    pub params: ExtendSectorExpirationParams,
+}
+
+/// Invokes `method` on a managed miner on behalf of the admin, as its owner.
+fn call_miner<P: Serialize>(miner: &Address, method: MethodNum, params: &P) -> Receipt {
+    let mut state = State::load();
+    state.validate_admin();
+    let miner = transfer::managed_miner(miner);
+
+    let params = match RawBytes::serialize(params) {
+        Ok(params) => params,
+        Err(err) => abort!(USR_SERIALIZATION, "failed to serialize params: {}", err),
+    };
+    let receipt = match guard::send_locked(
+        &mut state,
+        &miner,
+        method,
+        params,
+        TokenAmount::from_atto(0),
+    ) {
+        Ok(receipt) => receipt,
+        Err(err) => err.abort(),
+    };
+
+    state.record_event(EventPayload::MinerCalled(
+        miner,
+        method,
+        TokenAmount::from_atto(0),
+    ));
+    state.save();
+    receipt
+}
+
+/// Method num 39.
+/// Terminates sectors of a managed miner. Only the admin may call this.
+/// Returns a `TerminateSectorsReturn`.
+pub fn terminate_sectors(req: TerminateSectorsReq) -> TerminateSectorsReturn {
+    let receipt = call_miner(&req.miner, MINER_TERMINATE_SECTORS, &req.params);
+
+    match receipt.return_data.deserialize() {
+        Ok(ret) => ret,
+        Err(err) => abort!(
+            USR_SERIALIZATION,
+            "failed to parse terminate sectors return: {}",
+            err
+        ),
+    }
+}
+
+/// Method num 40.
+/// Declares sectors of a managed miner faulty. Only the admin may call this.
+pub fn declare_faults(req: DeclareFaultsReq) {
+    call_miner(&req.miner, MINER_DECLARE_FAULTS, &req.params);
+}
+
+/// Method num 41.
+/// Declares faulty sectors of a managed miner recovered. Only the admin may
+/// call this.
+pub fn declare_faults_recovered(req: DeclareFaultsRecoveredReq) {
+    call_miner(&req.miner, MINER_DECLARE_FAULTS_RECOVERED, &req.params);
+}
+
+/// Method num 42.
+/// Extends the expiration of sectors of a managed miner. Only the admin may
+/// call this.
+pub fn extend_sector_expiration(req: ExtendSectorExpirationReq) {
+    call_miner(&req.miner, MINER_EXTEND_SECTOR_EXPIRATION, &req.params);
+}
+
+#[cfg(test)]
+mod test {
+    use crate::runtime::MockRuntime;
+    use fvm_ipld_encoding::RawBytes;
+
+    #[test]
+    fn restricted_miner_methods_are_refused() {
+        use super::MINER_WITHDRAW_BALANCE;
+        use crate::multisig::{Operation, ProposeParams};
+        use crate::timelock::{QueueParams, TimelockAction};
+        use fvm_shared::address::Address;
+        use fvm_shared::econ::TokenAmount;
+        use fvm_shared::error::ExitCode;
+
+        let rt = MockRuntime::construct(10);
+        let miner = Address::new_id(1000);
+
+        // Allow-listing a restricted method can't be proposed, nor queued
+        // directly.
+        let allow = RawBytes::serialize(crate::SetMinerMethodParams {
+            method: MINER_WITHDRAW_BALANCE,
+            allowed: true,
+        })
+        .unwrap();
+        let abort = rt.call(22, crate::dispatch, allow).unwrap_err();
+        assert_eq!(abort.exit_code, ExitCode::USR_FORBIDDEN);
+        let params = RawBytes::serialize(ProposeParams {
+            op: Operation::SetMinerMethod(MINER_WITHDRAW_BALANCE, true),
+        })
+        .unwrap();
+        let abort = rt.call(23, crate::dispatch, params).unwrap_err();
+        assert_eq!(abort.exit_code, ExitCode::USR_FORBIDDEN);
+        let params = RawBytes::serialize(QueueParams {
+            action: TimelockAction::SetMinerMethod(MINER_WITHDRAW_BALANCE, true),
+        })
+        .unwrap();
+        let abort = rt.call(27, crate::dispatch, params).unwrap_err();
+        assert_eq!(abort.exit_code, ExitCode::USR_FORBIDDEN);
+        let state = crate::State::load();
+        assert_eq!((state.next_tx_id, state.next_queued_id), (0, 0));
+
+        // Even allow-listed, a withdrawal through `miner_call` is refused.
+        let mut state = crate::State::load();
+        state.add_miner(miner);
+        state.miner_methods.push(MINER_WITHDRAW_BALANCE);
+        state.save();
+        let params = RawBytes::serialize(crate::MinerCallParams {
+            miner,
+            method: MINER_WITHDRAW_BALANCE,
+            params: RawBytes::serialize(crate::WithdrawBalanceParams {
+                amount_requested: TokenAmount::from_atto(100),
+            })
+            .unwrap(),
+            value: TokenAmount::from_atto(0),
+        })
+        .unwrap();
+        let abort = rt.call(21, crate::dispatch, params).unwrap_err();
+        assert_eq!(abort.exit_code, ExitCode::USR_FORBIDDEN);
+        rt.verify();
+    }
+}
@@ -0,0 +1,395 @@
+//! M-of-N approval of sensitive operations, modelled on the builtin multisig
+//! actor but limited to this actor's own operations.
+
+use fvm_ipld_encoding::tuple::{Deserialize_tuple, Serialize_tuple};
+use fvm_ipld_encoding::RawBytes;
+use fvm_shared::address::Address;
+use fvm_shared::econ::TokenAmount;
+use fvm_shared::{ActorID, MethodNum};
+
+use crate::blockstore::Blockstore;
+use crate::events::EventPayload;
+use crate::map;
+use crate::miner;
+use crate::runtime::rt;
+use crate::timelock::{self, TimelockAction};
+use crate::transfer;
+use crate::State;
+
+tuple_enum! {
+    /// An operation that only runs once enough signers approved it.
+    #[derive(Clone, Debug, PartialEq)]
+    pub enum Operation {
+        /// Send funds out of the actor: (to, amount).
+        Withdraw(to: Address, amount: TokenAmount) = 0,
+        /// Confirm this actor as the new owner of a miner.
+        TakeOwner(miner: Address) = 1,
+        /// Queue a worker change of a managed miner: (miner, new worker).
+        ChangeWorker(miner: Address, worker: Address) = 2,
+        /// Withdraw available balance from a miner: (miner, amount).
+        WithdrawMiner(miner: Address, amount: TokenAmount) = 3,
+        /// Delete the actor, sending its balance to the preset beneficiary.
+        Destruct = 4,
+        /// Add a signer.
+        AddSigner(signer: ActorID) = 5,
+        /// Remove a signer, as long as enough remain to meet the threshold.
+        RemoveSigner(signer: ActorID) = 6,
+        /// Change the number of approvals operations need.
+        ChangeThreshold(threshold: u64) = 7,
+        /// Queue allowing or disallowing a miner method for `miner_call`.
+        SetMinerMethod(method: MethodNum, allowed: bool) = 8,
+        /// Send funds, and optionally a method call, to an actor whose code is
+        /// allow-listed: (to, amount, method, params).
+        Transfer(to: Address, amount: TokenAmount, method: MethodNum, params: RawBytes) = 9,
+        /// Change the largest amount the admin may `transfer` alone.
+        SetTransferLimit(limit: TokenAmount) = 10,
+    }
+}
+
+/// A proposed operation waiting for approvals.
+#[derive(Serialize_tuple, Deserialize_tuple, Clone, Debug, PartialEq)]
+pub struct Transaction {
+    pub op: Operation,
+    pub proposer: ActorID,
+    pub approved: Vec<ActorID>,
+}
+
+impl Transaction {
+    /// Whether the approvals of current signers reach the threshold. Approvals
+    /// of signers removed since then do not count.
+    pub fn is_approved(&self, signers: &[ActorID], threshold: u64) -> bool {
+        let approvals = self
+            .approved
+            .iter()
+            .filter(|id| signers.contains(id))
+            .count();
+        approvals as u64 >= threshold
+    }
+}
+
+#[derive(Debug, Serialize_tuple, Deserialize_tuple)]
+pub struct ProposeParams {
+    pub op: Operation,
+}
+
+#[derive(Debug, Serialize_tuple, Deserialize_tuple)]
+pub struct ProposeReturn {
+    pub tx_id: u64,
+    /// Whether the operation ran right away.
+    pub applied: bool,
+    /// Return value of the operation, if it ran.
+    pub ret: RawBytes,
+}
+
+#[derive(Debug, Serialize_tuple, Deserialize_tuple)]
+pub struct TxnIDParams {
+    pub tx_id: u64,
+}
+
+#[derive(Debug, Serialize_tuple, Deserialize_tuple)]
+pub struct ApproveReturn {
+    /// Whether this approval made the operation run.
+    pub applied: bool,
+    /// Return value of the operation, if it ran.
+    pub ret: RawBytes,
+}
+
+impl State {
+    /// Aborts unless the current message comes from a signer, and returns it.
+    pub fn validate_signer(&self) -> ActorID {
+        let caller = rt().caller();
+        if !self.signers.contains(&caller) {
+            abort!(USR_FORBIDDEN, "f0{} is not a signer", caller);
+        }
+        caller
+    }
+}
+
+/// Proposes `op` on behalf of the caller, who implicitly approves it. The
+/// operation runs right away if that approval reaches the threshold.
+pub fn propose_op(op: Operation) -> ProposeReturn {
+    let mut state = State::load();
+    let proposer = state.validate_signer();
+    if let Operation::SetMinerMethod(method, true) = op {
+        miner::validate_unrestricted(method);
+    }
+
+    let tx_id = state.next_tx_id;
+    state.next_tx_id += 1;
+    let tx = Transaction {
+        op,
+        proposer,
+        approved: vec![proposer],
+    };
+    state.record_event(EventPayload::TxProposed(tx_id));
+
+    if tx.is_approved(&state.signers, state.threshold) {
+        // Nothing is left pending, so persist the new id before running.
+        state.save();
+        let ret = execute(tx);
+        return ProposeReturn {
+            tx_id,
+            applied: true,
+            ret,
+        };
+    }
+
+    state.pending = match map::set(Blockstore, &state.pending, map::u64_key(tx_id), tx) {
+        Ok(pending) => pending,
+        Err(err) => abort!(USR_ILLEGAL_STATE, "{}", err),
+    };
+    state.save();
+    ProposeReturn {
+        tx_id,
+        applied: false,
+        ret: RawBytes::default(),
+    }
+}
+
+fn pending_tx(state: &State, tx_id: u64) -> Transaction {
+    match map::get(Blockstore, &state.pending, &map::u64_key(tx_id)) {
+        Ok(Some(tx)) => tx,
+        Ok(None) => abort!(USR_NOT_FOUND, "transaction {} not found", tx_id),
+        Err(err) => abort!(USR_ILLEGAL_STATE, "{}", err),
+    }
+}
+
+/// Method num 23.
+/// Proposes an operation. Returns a `ProposeReturn`.
+pub fn propose(params: ProposeParams) -> ProposeReturn {
+    propose_op(params.op)
+}
+
+/// Method num 24.
+/// Approves a pending operation, running it once the threshold is reached.
+/// Returns an `ApproveReturn`.
+pub fn approve(params: TxnIDParams) -> ApproveReturn {
+    let mut state = State::load();
+    let signer = state.validate_signer();
+    let mut tx = pending_tx(&state, params.tx_id);
+    if tx.approved.contains(&signer) {
+        abort!(
+            USR_FORBIDDEN,
+            "f0{} already approved transaction {}",
+            signer,
+            params.tx_id
+        );
+    }
+    tx.approved.push(signer);
+    state.record_event(EventPayload::TxApproved(params.tx_id));
+
+    let applied = tx.is_approved(&state.signers, state.threshold);
+    let pending = if applied {
+        // Drop the transaction before running it, so it can't run twice.
+        map::remove::<Transaction>(Blockstore, &state.pending, &map::u64_key(params.tx_id))
+    } else {
+        map::set(
+            Blockstore,
+            &state.pending,
+            map::u64_key(params.tx_id),
+            tx.clone(),
+        )
+    };
+    state.pending = match pending {
+        Ok(pending) => pending,
+        Err(err) => abort!(USR_ILLEGAL_STATE, "{}", err),
+    };
+    state.save();
+
+    let ret = if applied {
+        execute(tx)
+    } else {
+        RawBytes::default()
+    };
+    ApproveReturn { applied, ret }
+}
+
+/// Method num 25.
+/// Cancels a pending operation. Only its proposer may cancel it.
+pub fn cancel(params: TxnIDParams) {
+    let mut state = State::load();
+    let signer = state.validate_signer();
+    let tx = pending_tx(&state, params.tx_id);
+    if tx.proposer != signer {
+        abort!(
+            USR_FORBIDDEN,
+            "only the proposer f0{} may cancel transaction {}",
+            tx.proposer,
+            params.tx_id
+        );
+    }
+
+    state.pending =
+        match map::remove::<Transaction>(Blockstore, &state.pending, &map::u64_key(params.tx_id)) {
+            Ok(pending) => pending,
+            Err(err) => abort!(USR_ILLEGAL_STATE, "{}", err),
+        };
+    state.record_event(EventPayload::TxCancelled(params.tx_id));
+    state.save();
+}
+
+/// Method num 26.
+/// Returns pending transaction `tx_id`.
+pub fn get_transaction(params: TxnIDParams) -> Transaction {
+    let state = State::load();
+    pending_tx(&state, params.tx_id)
+}
+
+/// Runs an approved operation and returns its return value.
+fn execute(tx: Transaction) -> RawBytes {
+    match tx.op {
+        Operation::Withdraw(to, amount) => crate::exec_withdraw(tx.proposer, to, amount),
+        Operation::TakeOwner(miner) => crate::exec_take_owner(miner),
+        Operation::ChangeWorker(miner, worker) => {
+            // Worker changes also wait for the timelock delay.
+            let ret = timelock::enqueue(TimelockAction::ChangeWorker(miner, worker));
+            crate::serialize_return(&ret)
+        }
+        Operation::SetMinerMethod(method, allowed) => {
+            // So do changes to the `miner_call` allow-list.
+            let ret = timelock::enqueue(TimelockAction::SetMinerMethod(method, allowed));
+            crate::serialize_return(&ret)
+        }
+        Operation::WithdrawMiner(miner, amount) => {
+            crate::exec_withdraw_miner(tx.proposer, miner, amount)
+        }
+        Operation::Transfer(to, amount, method, params) => {
+            transfer::exec_transfer(tx.proposer, to, amount, method, params)
+        }
+        Operation::SetTransferLimit(limit) => {
+            let mut state = State::load();
+            state.transfer_limit = limit.clone();
+            state.record_event(EventPayload::TransferLimitChanged(limit));
+            state.save();
+            RawBytes::default()
+        }
+        Operation::Destruct => crate::exec_destruct(),
+        Operation::AddSigner(signer) => {
+            let mut state = State::load();
+            if !state.signers.contains(&signer) {
+                state.signers.push(signer);
+            }
+            state.record_event(EventPayload::SignerAdded(signer));
+            state.save();
+            RawBytes::default()
+        }
+        Operation::RemoveSigner(signer) => {
+            let mut state = State::load();
+            state.signers.retain(|id| *id != signer);
+            if (state.signers.len() as u64) < state.threshold {
+                abort!(
+                    USR_ILLEGAL_ARGUMENT,
+                    "removing f0{} leaves fewer signers than the threshold {}",
+                    signer,
+                    state.threshold
+                );
+            }
+            state.record_event(EventPayload::SignerRemoved(signer));
+            state.save();
+            RawBytes::default()
+        }
+        Operation::ChangeThreshold(threshold) => {
+            let mut state = State::load();
+            if threshold == 0 || threshold > state.signers.len() as u64 {
+                abort!(
+                    USR_ILLEGAL_ARGUMENT,
+                    "threshold {} must be between 1 and {}",
+                    threshold,
+                    state.signers.len()
+                );
+            }
+            state.threshold = threshold;
+            state.record_event(EventPayload::ThresholdChanged(threshold));
+            state.save();
+            RawBytes::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::runtime::{ok_receipt, MockRuntime};
+    use fvm_ipld_encoding::RawBytes;
+
+    #[test]
+    fn only_current_signers_count_towards_threshold() {
+        let tx = super::Transaction {
+            op: super::Operation::Destruct,
+            proposer: 100,
+            approved: vec![100, 101],
+        };
+
+        assert!(tx.is_approved(&[100, 101, 102], 2));
+        assert!(!tx.is_approved(&[100, 101, 102], 3));
+        // 101 was removed from the signers after approving.
+        assert!(!tx.is_approved(&[100, 102], 2));
+    }
+
+    #[test]
+    fn approval_reentered_from_the_callee_is_rejected() {
+        use super::TxnIDParams;
+        use crate::guard::ERR_REENTRANT;
+        use fvm_shared::address::Address;
+        use fvm_shared::econ::TokenAmount;
+
+        let rt = MockRuntime::construct(10);
+        rt.set_balance(TokenAmount::from_atto(1000));
+        let mut state = crate::State::load();
+        state.signers = vec![10, 11];
+        state.threshold = 2;
+        state.save();
+
+        // Two withdrawals to f010 wait for the second signer.
+        for amount in [100, 200] {
+            let params = RawBytes::serialize((TokenAmount::from_atto(amount),)).unwrap();
+            rt.call(12, crate::dispatch, params).unwrap();
+        }
+
+        rt.expect_send(
+            Address::new_id(10),
+            fvm_shared::METHOD_SEND,
+            RawBytes::default(),
+            TokenAmount::from_atto(100),
+            ok_receipt(RawBytes::default()),
+        );
+        let callee = rt.clone();
+        rt.on_next_send(move || {
+            // The first withdrawal was dropped from the pending ones and
+            // recorded before the send.
+            let state = crate::State::load();
+            assert!(state.locked);
+            assert!(crate::map::get::<super::Transaction>(
+                crate::blockstore::Blockstore,
+                &state.pending,
+                &crate::map::u64_key(0)
+            )
+            .unwrap()
+            .is_none());
+            assert_eq!(*callee.balance.borrow(), TokenAmount::from_atto(900));
+
+            // The recipient, a signer, approves the second one from its
+            // callback.
+            callee.set_caller(11);
+            let params = RawBytes::serialize(TxnIDParams { tx_id: 1 }).unwrap();
+            let abort = callee.call(24, crate::dispatch, params).unwrap_err();
+            assert_eq!(abort.exit_code, ERR_REENTRANT);
+        });
+
+        rt.set_caller(11);
+        let params = RawBytes::serialize(TxnIDParams { tx_id: 0 }).unwrap();
+        rt.call(24, crate::dispatch, params).unwrap();
+        rt.verify();
+
+        // The second withdrawal is still pending.
+        let state = crate::State::load();
+        assert!(!state.locked);
+        assert!(crate::map::get::<super::Transaction>(
+            crate::blockstore::Blockstore,
+            &state.pending,
+            &crate::map::u64_key(1)
+        )
+        .unwrap()
+        .is_some());
+        assert_eq!(*rt.balance.borrow(), TokenAmount::from_atto(900));
+    }
+}
@@ -0,0 +1,47 @@
+//! Emergency pause. While paused, methods that move value out of the actor or
+//! create miners abort, while queries keep working. Only the guardian may
+//! pause or unpause; the guardian itself is changed through the timelock.
+
+use fvm_ipld_encoding::tuple::{Deserialize_tuple, Serialize_tuple};
+use fvm_shared::error::ExitCode;
+
+use crate::events::EventPayload;
+use crate::runtime::rt;
+use crate::State;
+
+/// Exit code for value-moving methods called while the actor is paused.
+pub const ERR_PAUSED: ExitCode = ExitCode::new(33);
+
+impl State {
+    /// Aborts unless the current message comes from the guardian.
+    pub fn validate_guardian(&self) {
+        let caller = rt().caller();
+        if caller != self.guardian {
+            abort!(USR_FORBIDDEN, "f0{} is not the guardian", caller);
+        }
+    }
+
+    /// Aborts with `ERR_PAUSED` while the actor is paused.
+    pub fn validate_not_paused(&self) {
+        if self.paused {
+            abort!(ERR_PAUSED => "actor is paused");
+        }
+    }
+}
+
+#[derive(Debug, Serialize_tuple, Deserialize_tuple)]
+pub struct SetPausedParams {
+    pub paused: bool,
+}
+
+/// Method num 32.
+/// Pauses or unpauses the actor. Only the guardian may call this.
+pub fn set_paused(params: SetPausedParams) {
+    let mut state = State::load();
+    state.validate_guardian();
+    if state.paused != params.paused {
+        state.paused = params.paused;
+        state.record_event(EventPayload::PausedChanged(params.paused));
+        state.save();
+    }
+}
@@ -0,0 +1,201 @@
+//! Limits on how much value can leave the actor per period, both globally and
+//! per initiating account. Spending is tracked in buckets aligned to multiples
+//! of the period, which reset once the chain moves past them.
+
+use fvm_ipld_encoding::tuple::{Deserialize_tuple, Serialize_tuple};
+use fvm_shared::clock::ChainEpoch;
+use fvm_shared::econ::TokenAmount;
+use fvm_shared::error::ExitCode;
+use fvm_shared::ActorID;
+
+use crate::blockstore::Blockstore;
+use crate::map;
+use crate::runtime::rt;
+use crate::State;
+
+/// Exit code for withdrawals exceeding a rate limit.
+pub const ERR_RATE_LIMITED: ExitCode = ExitCode::new(32);
+
+/// At most `max_amount` may leave the actor per `period` epochs.
+#[derive(Serialize_tuple, Deserialize_tuple, Clone, Debug, PartialEq)]
+pub struct RateLimit {
+    pub max_amount: TokenAmount,
+    pub period: ChainEpoch,
+}
+
+/// Amount spent in the bucket starting at `start`.
+#[derive(Serialize_tuple, Deserialize_tuple, Clone, Debug, Default, PartialEq)]
+pub struct Bucket {
+    pub start: ChainEpoch,
+    pub spent: TokenAmount,
+}
+
+impl RateLimit {
+    /// Start of the bucket containing `epoch`.
+    pub fn bucket_start(&self, epoch: ChainEpoch) -> ChainEpoch {
+        epoch - epoch.rem_euclid(self.period)
+    }
+
+    /// Amount that may still be spent at `epoch`, given the last bucket.
+    pub fn remaining(&self, bucket: &Bucket, epoch: ChainEpoch) -> TokenAmount {
+        if bucket.start != self.bucket_start(epoch) {
+            return self.max_amount.clone();
+        }
+        if bucket.spent >= self.max_amount {
+            return TokenAmount::from_atto(0);
+        }
+        self.max_amount.clone() - bucket.spent.clone()
+    }
+
+    /// Returns the bucket after spending `amount` at `epoch`, or the remaining
+    /// allowance if `amount` exceeds it.
+    pub fn spend(
+        &self,
+        bucket: &Bucket,
+        epoch: ChainEpoch,
+        amount: &TokenAmount,
+    ) -> Result<Bucket, TokenAmount> {
+        let remaining = self.remaining(bucket, epoch);
+        if *amount > remaining {
+            return Err(remaining);
+        }
+        let start = self.bucket_start(epoch);
+        let spent = if bucket.start == start {
+            bucket.spent.clone() + amount.clone()
+        } else {
+            amount.clone()
+        };
+        Ok(Bucket { start, spent })
+    }
+
+    /// Aborts if the limit can't be enforced.
+    pub fn validate(&self) {
+        if self.period <= 0 {
+            abort!(
+                USR_ILLEGAL_ARGUMENT,
+                "rate limit period {} must be positive",
+                self.period
+            );
+        }
+        if self.max_amount < TokenAmount::from_atto(0) {
+            abort!(
+                USR_ILLEGAL_ARGUMENT,
+                "rate limit amount {} must not be negative",
+                self.max_amount
+            );
+        }
+    }
+}
+
+fn caller_bucket(state: &State, caller: ActorID) -> Bucket {
+    match map::get(Blockstore, &state.caller_buckets, &map::u64_key(caller)) {
+        Ok(bucket) => bucket.unwrap_or_default(),
+        Err(err) => abort!(USR_ILLEGAL_STATE, "{}", err),
+    }
+}
+
+impl State {
+    /// Charges `amount` leaving the actor on behalf of `caller` against the
+    /// global and per-caller limits, aborting with `ERR_RATE_LIMITED` if
+    /// either is exceeded. The caller saves the state.
+    pub fn charge_rate_limits(&mut self, caller: ActorID, amount: &TokenAmount) {
+        if *amount < TokenAmount::from_atto(0) {
+            abort!(USR_ILLEGAL_ARGUMENT, "negative amount {}", amount);
+        }
+        let epoch = rt().curr_epoch();
+
+        if let Some(limit) = &self.global_limit {
+            self.global_bucket = match limit.spend(&self.global_bucket, epoch, amount) {
+                Ok(bucket) => bucket,
+                Err(remaining) => abort!(
+                    ERR_RATE_LIMITED => "amount {} exceeds the remaining global allowance {}",
+                    amount,
+                    remaining
+                ),
+            };
+        }
+
+        if let Some(limit) = &self.caller_limit {
+            let bucket = caller_bucket(self, caller);
+            let bucket = match limit.spend(&bucket, epoch, amount) {
+                Ok(bucket) => bucket,
+                Err(remaining) => abort!(
+                    ERR_RATE_LIMITED => "amount {} exceeds the remaining allowance {} of f0{}",
+                    amount,
+                    remaining,
+                    caller
+                ),
+            };
+            self.caller_buckets = match map::set(
+                Blockstore,
+                &self.caller_buckets,
+                map::u64_key(caller),
+                bucket,
+            ) {
+                Ok(buckets) => buckets,
+                Err(err) => abort!(USR_ILLEGAL_STATE, "{}", err),
+            };
+        }
+    }
+}
+
+#[derive(Debug, Serialize_tuple, Deserialize_tuple)]
+pub struct AllowanceParams {
+    pub caller: ActorID,
+}
+
+#[derive(Debug, Serialize_tuple, Deserialize_tuple)]
+pub struct AllowanceReturn {
+    /// Remaining global allowance, `None` if unlimited.
+    pub global: Option<TokenAmount>,
+    /// Remaining allowance of the caller, `None` if unlimited.
+    pub caller: Option<TokenAmount>,
+}
+
+/// Method num 31.
+/// Returns the remaining allowance of an account in the current period.
+pub fn get_allowance(params: AllowanceParams) -> AllowanceReturn {
+    let state = State::load();
+    let epoch = rt().curr_epoch();
+    AllowanceReturn {
+        global: state
+            .global_limit
+            .as_ref()
+            .map(|limit| limit.remaining(&state.global_bucket, epoch)),
+        caller: state
+            .caller_limit
+            .as_ref()
+            .map(|limit| limit.remaining(&caller_bucket(&state, params.caller), epoch)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    #[test]
+    fn rate_limit_buckets_reset_each_period() {
+        use fvm_shared::econ::TokenAmount;
+
+        let limit = super::RateLimit {
+            max_amount: TokenAmount::from_atto(100),
+            period: 10,
+        };
+        let bucket = super::Bucket::default();
+
+        let bucket = limit
+            .spend(&bucket, 12, &TokenAmount::from_atto(60))
+            .unwrap();
+        assert_eq!(bucket.start, 10);
+        assert_eq!(limit.remaining(&bucket, 19), TokenAmount::from_atto(40));
+        assert_eq!(
+            limit.spend(&bucket, 19, &TokenAmount::from_atto(41)),
+            Err(TokenAmount::from_atto(40))
+        );
+
+        // The next period starts with the full allowance again.
+        assert_eq!(limit.remaining(&bucket, 20), TokenAmount::from_atto(100));
+        let bucket = limit
+            .spend(&bucket, 20, &TokenAmount::from_atto(100))
+            .unwrap();
+        assert_eq!(bucket.spent, TokenAmount::from_atto(100));
+    }
+}
@@ -0,0 +1,427 @@
+//! The environment the actor runs in: message context, its own state root
+//! and balance, other actors and sends. Methods reach it through `rt()`,
+//! which is the FVM unless a test installed a `MockRuntime`.
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use cid::Cid;
+use fvm_ipld_encoding::RawBytes;
+use fvm_sdk as sdk;
+use fvm_shared::address::Address;
+use fvm_shared::clock::ChainEpoch;
+use fvm_shared::econ::TokenAmount;
+use fvm_shared::error::ErrorNumber;
+use fvm_shared::receipt::Receipt;
+use fvm_shared::{ActorID, MethodNum};
+
+#[cfg(not(target_arch = "wasm32"))]
+pub use self::mock::{ok_receipt, Abort, ExpectedSend, MockRuntime};
+
+pub trait Runtime {
+    /// The immediate caller of the current message.
+    fn caller(&self) -> ActorID;
+    /// The account that sent the message the current call is part of.
+    fn origin(&self) -> ActorID;
+    /// This actor.
+    fn receiver(&self) -> ActorID;
+    fn method_number(&self) -> MethodNum;
+    fn value_received(&self) -> TokenAmount;
+    /// Codec and bytes of the parameters block `id`.
+    fn params_raw(&self, id: u32) -> Result<(u64, Vec<u8>), ErrorNumber>;
+    fn curr_epoch(&self) -> ChainEpoch;
+
+    fn root(&self) -> Result<Cid, ErrorNumber>;
+    fn set_root(&self, root: &Cid) -> Result<(), ErrorNumber>;
+    fn current_balance(&self) -> TokenAmount;
+    /// Deletes the actor, sending its balance to `beneficiary`.
+    fn self_destruct(&self, beneficiary: &Address) -> Result<(), ErrorNumber>;
+
+    fn resolve_address(&self, addr: &Address) -> Option<ActorID>;
+    fn get_actor_code_cid(&self, addr: &Address) -> Option<Cid>;
+    fn send(
+        &self,
+        to: &Address,
+        method: MethodNum,
+        params: RawBytes,
+        value: TokenAmount,
+    ) -> Result<Receipt, ErrorNumber>;
+
+    /// Ends the current message with `code`, reverting its state changes.
+    fn abort(&self, code: u32, message: Option<&str>) -> !;
+}
+
+/// The runtime provided by the FVM through syscalls.
+pub struct FvmRuntime;
+
+impl Runtime for FvmRuntime {
+    fn caller(&self) -> ActorID {
+        sdk::message::caller()
+    }
+
+    fn origin(&self) -> ActorID {
+        sdk::message::origin()
+    }
+
+    fn receiver(&self) -> ActorID {
+        sdk::message::receiver()
+    }
+
+    fn method_number(&self) -> MethodNum {
+        sdk::message::method_number()
+    }
+
+    fn value_received(&self) -> TokenAmount {
+        sdk::message::value_received()
+    }
+
+    fn params_raw(&self, id: u32) -> Result<(u64, Vec<u8>), ErrorNumber> {
+        sdk::message::params_raw(id)
+    }
+
+    fn curr_epoch(&self) -> ChainEpoch {
+        sdk::network::curr_epoch()
+    }
+
+    fn root(&self) -> Result<Cid, ErrorNumber> {
+        // The only failure is the actor having been deleted.
+        sdk::sself::root().map_err(|_| ErrorNumber::IllegalOperation)
+    }
+
+    fn set_root(&self, root: &Cid) -> Result<(), ErrorNumber> {
+        sdk::sself::set_root(root).map_err(|_| ErrorNumber::IllegalOperation)
+    }
+
+    fn current_balance(&self) -> TokenAmount {
+        sdk::sself::current_balance()
+    }
+
+    fn self_destruct(&self, beneficiary: &Address) -> Result<(), ErrorNumber> {
+        sdk::sself::self_destruct(beneficiary).map_err(|_| ErrorNumber::IllegalOperation)
+    }
+
+    fn resolve_address(&self, addr: &Address) -> Option<ActorID> {
+        sdk::actor::resolve_address(addr)
+    }
+
+    fn get_actor_code_cid(&self, addr: &Address) -> Option<Cid> {
+        sdk::actor::get_actor_code_cid(addr)
+    }
+
+    fn send(
+        &self,
+        to: &Address,
+        method: MethodNum,
+        params: RawBytes,
+        value: TokenAmount,
+    ) -> Result<Receipt, ErrorNumber> {
+        sdk::send::send(to, method, params, value)
+    }
+
+    fn abort(&self, code: u32, message: Option<&str>) -> ! {
+        sdk::vm::abort(code, message)
+    }
+}
+
+thread_local! {
+    static RUNTIME: RefCell<Rc<dyn Runtime>> = RefCell::new(Rc::new(FvmRuntime));
+}
+
+/// The runtime of the current message.
+pub fn rt() -> Rc<dyn Runtime> {
+    RUNTIME.with(|rt| rt.borrow().clone())
+}
+
+/// Makes `runtime` the runtime of the following calls on this thread,
+/// returning the previous one.
+pub fn set_runtime(runtime: Rc<dyn Runtime>) -> Rc<dyn Runtime> {
+    RUNTIME.with(|rt| rt.replace(runtime))
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+mod mock {
+    use std::cell::{Cell, RefCell};
+    use std::collections::{HashMap, VecDeque};
+    use std::panic::{self, AssertUnwindSafe};
+    use std::rc::Rc;
+
+    use cid::multihash::Code;
+    use cid::Cid;
+    use fvm_ipld_encoding::{CborStore, RawBytes, DAG_CBOR};
+    use fvm_shared::address::Address;
+    use fvm_shared::clock::ChainEpoch;
+    use fvm_shared::econ::TokenAmount;
+    use fvm_shared::error::{ErrorNumber, ExitCode};
+    use fvm_shared::receipt::Receipt;
+    use fvm_shared::{ActorID, MethodNum};
+
+    use super::Runtime;
+    use crate::blockstore::Blockstore;
+
+    /// How a call on a `MockRuntime` ended when it did not return.
+    #[derive(Clone, Debug, PartialEq)]
+    pub struct Abort {
+        pub exit_code: ExitCode,
+        pub message: String,
+    }
+
+    /// A send the code under test must make next, and what it gets back.
+    #[derive(Clone, Debug)]
+    pub struct ExpectedSend {
+        pub to: Address,
+        pub method: MethodNum,
+        pub params: RawBytes,
+        pub value: TokenAmount,
+        pub receipt: Result<Receipt, ErrorNumber>,
+    }
+
+    /// A successful receipt returning `return_data`, for an `ExpectedSend`.
+    pub fn ok_receipt(return_data: RawBytes) -> Result<Receipt, ErrorNumber> {
+        Ok(Receipt {
+            exit_code: ExitCode::OK,
+            return_data,
+            gas_used: 0,
+        })
+    }
+
+    /// A runtime for native tests. The message context, balances and actors
+    /// are set up by the test, sends must match the expected ones in order
+    /// and return their scripted receipts (or `unexpected_sends`, once none
+    /// are left), and aborts unwind to `call`.
+    #[derive(Default)]
+    pub struct MockRuntime {
+        pub caller: Cell<ActorID>,
+        pub origin: Cell<ActorID>,
+        pub receiver: Cell<ActorID>,
+        pub method: Cell<MethodNum>,
+        pub value_received: RefCell<TokenAmount>,
+        pub params: RefCell<RawBytes>,
+        pub epoch: Cell<ChainEpoch>,
+        pub root: Cell<Option<Cid>>,
+        pub balance: RefCell<TokenAmount>,
+        /// Actors by address, with their code CID.
+        pub actors: RefCell<HashMap<Address, (ActorID, Cid)>>,
+        pub expected_sends: RefCell<VecDeque<ExpectedSend>>,
+        /// What sends nobody expected get back. If unset, they fail the test.
+        pub unexpected_sends: RefCell<Option<Result<Receipt, ErrorNumber>>>,
+        /// The beneficiary, once the actor deleted itself.
+        pub destructed: Cell<Option<Address>>,
+        /// Runs as the callee of the next send, see `on_next_send`.
+        pub callee: RefCell<Option<Box<dyn FnOnce()>>>,
+    }
+
+    impl MockRuntime {
+        /// Installs a fresh mock as the runtime of this thread, for the actor
+        /// `receiver`, and returns it. Like an actor the FVM just created, its
+        /// root is the empty array.
+        pub fn install(receiver: ActorID) -> Rc<Self> {
+            let rt = Rc::new(Self::default());
+            rt.receiver.set(receiver);
+            let empty = Blockstore.put_cbor(&Vec::<()>::new(), Code::Blake2b256);
+            rt.root.set(Some(empty.unwrap()));
+            super::set_runtime(rt.clone());
+            rt
+        }
+
+        /// Installs a fresh mock for the actor f0100 and runs the constructor
+        /// on behalf of `admin`, who is also the caller of the following
+        /// calls.
+        pub fn construct(admin: ActorID) -> Rc<Self> {
+            let rt = Self::install(100);
+            rt.caller.set(1);
+            rt.origin.set(admin);
+            assert_eq!(rt.call(1, crate::dispatch, RawBytes::default()), Ok(None));
+            rt.set_caller(admin);
+            rt
+        }
+
+        /// Makes `caller` both the caller and the origin of the next calls.
+        pub fn set_caller(&self, caller: ActorID) {
+            self.caller.set(caller);
+            self.origin.set(caller);
+        }
+
+        pub fn set_balance(&self, balance: TokenAmount) {
+            self.balance.replace(balance);
+        }
+
+        /// Adds an actor that `addr` resolves to.
+        pub fn add_actor(&self, addr: Address, id: ActorID, code: Cid) {
+            let mut actors = self.actors.borrow_mut();
+            actors.insert(addr, (id, code));
+            actors.insert(Address::new_id(id), (id, code));
+        }
+
+        /// Expects the next send to match `to`, `method`, `params` and
+        /// `value`, and answers it with `receipt`.
+        pub fn expect_send(
+            &self,
+            to: Address,
+            method: MethodNum,
+            params: RawBytes,
+            value: TokenAmount,
+            receipt: Result<Receipt, ErrorNumber>,
+        ) {
+            self.expected_sends.borrow_mut().push_back(ExpectedSend {
+                to,
+                method,
+                params,
+                value,
+                receipt,
+            });
+        }
+
+        /// Makes the next send run `callee` once the value moved, before
+        /// returning its receipt, like an actor that calls back into this
+        /// one. The message context is restored afterwards.
+        pub fn on_next_send(&self, callee: impl FnOnce() + 'static) {
+            self.callee.replace(Some(Box::new(callee)));
+        }
+
+        /// Runs `method` as method number `number` with `params`, returning
+        /// what it returned or how it aborted. Like in the FVM, an abort
+        /// drops the state changes of the call.
+        pub fn call<F>(
+            &self,
+            number: MethodNum,
+            method: F,
+            params: RawBytes,
+        ) -> Result<Option<RawBytes>, Abort>
+        where
+            F: FnOnce(u32) -> Option<RawBytes>,
+        {
+            self.method.set(number);
+            self.params.replace(params);
+            let root = self.root.get();
+            match panic::catch_unwind(AssertUnwindSafe(|| method(0))) {
+                Ok(ret) => Ok(ret),
+                Err(payload) => match payload.downcast::<Abort>() {
+                    Ok(abort) => {
+                        self.root.set(root);
+                        Err(*abort)
+                    }
+                    Err(payload) => panic::resume_unwind(payload),
+                },
+            }
+        }
+
+        /// Asserts that every expected send happened.
+        pub fn verify(&self) {
+            let missing = self.expected_sends.borrow();
+            assert!(missing.is_empty(), "expected sends not made: {:?}", missing);
+        }
+    }
+
+    impl Runtime for MockRuntime {
+        fn caller(&self) -> ActorID {
+            self.caller.get()
+        }
+
+        fn origin(&self) -> ActorID {
+            self.origin.get()
+        }
+
+        fn receiver(&self) -> ActorID {
+            self.receiver.get()
+        }
+
+        fn method_number(&self) -> MethodNum {
+            self.method.get()
+        }
+
+        fn value_received(&self) -> TokenAmount {
+            self.value_received.borrow().clone()
+        }
+
+        fn params_raw(&self, _id: u32) -> Result<(u64, Vec<u8>), ErrorNumber> {
+            Ok((DAG_CBOR, self.params.borrow().bytes().to_vec()))
+        }
+
+        fn curr_epoch(&self) -> ChainEpoch {
+            self.epoch.get()
+        }
+
+        fn root(&self) -> Result<Cid, ErrorNumber> {
+            self.root.get().ok_or(ErrorNumber::IllegalOperation)
+        }
+
+        fn set_root(&self, root: &Cid) -> Result<(), ErrorNumber> {
+            self.root.set(Some(*root));
+            Ok(())
+        }
+
+        fn current_balance(&self) -> TokenAmount {
+            self.balance.borrow().clone()
+        }
+
+        fn self_destruct(&self, beneficiary: &Address) -> Result<(), ErrorNumber> {
+            self.destructed.set(Some(*beneficiary));
+            self.balance.replace(TokenAmount::from_atto(0));
+            Ok(())
+        }
+
+        fn resolve_address(&self, addr: &Address) -> Option<ActorID> {
+            // Like the FVM, ID addresses resolve to themselves.
+            if let Ok(id) = addr.id() {
+                return Some(id);
+            }
+            self.actors.borrow().get(addr).map(|(id, _)| *id)
+        }
+
+        fn get_actor_code_cid(&self, addr: &Address) -> Option<Cid> {
+            self.actors.borrow().get(addr).map(|(_, code)| *code)
+        }
+
+        fn send(
+            &self,
+            to: &Address,
+            method: MethodNum,
+            params: RawBytes,
+            value: TokenAmount,
+        ) -> Result<Receipt, ErrorNumber> {
+            let expected = self.expected_sends.borrow_mut().pop_front();
+            let receipt = match (expected, self.unexpected_sends.borrow().clone()) {
+                (Some(expected), _) => {
+                    assert_eq!(
+                        (
+                            &expected.to,
+                            expected.method,
+                            &expected.params,
+                            &expected.value
+                        ),
+                        (to, method, &params, &value),
+                        "send does not match the expected one"
+                    );
+                    expected.receipt
+                }
+                (None, Some(receipt)) => receipt,
+                (None, None) => panic!("unexpected send to {} method {}", to, method),
+            };
+            // Value only moves if the callee succeeded.
+            if matches!(&receipt, Ok(receipt) if receipt.exit_code.is_success()) {
+                let balance = self.balance.borrow().clone() - value;
+                self.balance.replace(balance);
+            }
+            let callee = self.callee.borrow_mut().take();
+            if let Some(callee) = callee {
+                let caller = self.caller.get();
+                let origin = self.origin.get();
+                let method = self.method.get();
+                let params = self.params.borrow().clone();
+                callee();
+                self.caller.set(caller);
+                self.origin.set(origin);
+                self.method.set(method);
+                self.params.replace(params);
+            }
+            receipt
+        }
+
+        fn abort(&self, code: u32, message: Option<&str>) -> ! {
+            panic::panic_any(Abort {
+                exit_code: ExitCode::new(code),
+                message: message.unwrap_or_default().to_string(),
+            })
+        }
+    }
+}
@@ -0,0 +1,113 @@
+use fvm_ipld_encoding::RawBytes;
+use fvm_shared::address::Address;
+use fvm_shared::econ::TokenAmount;
+use fvm_shared::error::{ErrorNumber, ExitCode};
+use fvm_shared::receipt::Receipt;
+use fvm_shared::MethodNum;
+
+use crate::runtime::rt;
+
+/// Exit codes below this value are reserved for the system. Actors cannot
+/// abort with them.
+const FIRST_USER_EXIT_CODE: u32 = 16;
+
+/// A send that did not succeed, with everything needed to explain why.
+#[derive(Debug)]
+pub enum SendError {
+    /// The send syscall itself failed, so the callee never ran.
+    Syscall {
+        to: Address,
+        method: MethodNum,
+        error: ErrorNumber,
+    },
+    /// The callee ran and exited with a non-success code.
+    Exit {
+        to: Address,
+        method: MethodNum,
+        exit_code: ExitCode,
+        return_data: RawBytes,
+    },
+}
+
+impl SendError {
+    /// The exit code this actor aborts with when it gives up on the send.
+    ///
+    /// - A callee exit code in the user range is propagated unchanged.
+    /// - A callee exit code in the system range becomes `USR_ILLEGAL_STATE`,
+    ///   since actors may not abort with system codes.
+    /// - A syscall error maps to the closest user exit code, falling back to
+    ///   `USR_ILLEGAL_STATE`.
+    pub fn exit_code(&self) -> ExitCode {
+        match self {
+            SendError::Exit { exit_code, .. } if exit_code.value() >= FIRST_USER_EXIT_CODE => {
+                *exit_code
+            }
+            SendError::Exit { .. } => ExitCode::USR_ILLEGAL_STATE,
+            SendError::Syscall { error, .. } => match error {
+                ErrorNumber::InsufficientFunds => ExitCode::USR_INSUFFICIENT_FUNDS,
+                ErrorNumber::NotFound => ExitCode::USR_NOT_FOUND,
+                ErrorNumber::IllegalArgument => ExitCode::USR_ILLEGAL_ARGUMENT,
+                ErrorNumber::Forbidden => ExitCode::USR_FORBIDDEN,
+                _ => ExitCode::USR_ILLEGAL_STATE,
+            },
+        }
+    }
+
+    /// Aborts the current message with `exit_code()`, keeping the original
+    /// exit code and return data in the message.
+    pub fn abort(&self) -> ! {
+        match self {
+            SendError::Syscall { to, method, error } => abort!(
+                self.exit_code() => "send to {} method {} failed: {:?}",
+                to,
+                method,
+                error
+            ),
+            SendError::Exit {
+                to,
+                method,
+                exit_code,
+                return_data,
+            } => abort!(
+                self.exit_code() => "send to {} method {} exited with {}, return data {:?}",
+                to,
+                method,
+                exit_code,
+                return_data
+            ),
+        }
+    }
+}
+
+/// Sends a message, turning a non-success receipt into a `SendError` that
+/// keeps the callee's exit code and return data.
+pub fn send(
+    to: &Address,
+    method: MethodNum,
+    params: RawBytes,
+    value: TokenAmount,
+) -> Result<Receipt, SendError> {
+    log!("send to {} method {} value {}", to, method, value);
+    let receipt = rt().send(to, method, params, value);
+    log!(
+        "send to {} method {} => {:?}",
+        to,
+        method,
+        receipt.as_ref().map(|r| r.exit_code)
+    );
+
+    match receipt {
+        Ok(receipt) if receipt.exit_code.is_success() => Ok(receipt),
+        Ok(receipt) => Err(SendError::Exit {
+            to: *to,
+            method,
+            exit_code: receipt.exit_code,
+            return_data: receipt.return_data,
+        }),
+        Err(error) => Err(SendError::Syscall {
+            to: *to,
+            method,
+            error,
+        }),
+    }
+}
@@ -0,0 +1,289 @@
+//! Delayed execution of admin actions. Actions are queued with an earliest
+//! execution epoch, can be cancelled by the admin until they run, and can be
+//! executed by anyone once the delay has passed.
+
+use cid::Cid;
+use fvm_ipld_encoding::tuple::{Deserialize_tuple, Serialize_tuple};
+use fvm_ipld_encoding::RawBytes;
+use fvm_shared::address::Address;
+use fvm_shared::clock::ChainEpoch;
+use fvm_shared::{ActorID, MethodNum};
+
+use crate::blockstore::Blockstore;
+use crate::events::EventPayload;
+use crate::map;
+use crate::miner;
+use crate::ratelimit::RateLimit;
+use crate::runtime::rt;
+use crate::State;
+
+/// Delay of a freshly constructed actor, one day of epochs.
+pub const DEFAULT_TIMELOCK_DELAY: ChainEpoch = 2880;
+
+/// Upper bound for the delay, so that a bad `SetDelay` can't lock the admin
+/// out for good.
+pub const MAX_TIMELOCK_DELAY: ChainEpoch = 30 * 2880;
+
+tuple_enum! {
+    /// An admin action that only takes effect after the timelock delay.
+    #[derive(Clone, Debug, PartialEq)]
+    pub enum TimelockAction {
+        /// Change the worker of a managed miner: (miner, new worker).
+        ChangeWorker(miner: Address, worker: Address) = 0,
+        /// Allow or disallow a miner method for `miner_call`.
+        SetMinerMethod(method: MethodNum, allowed: bool) = 1,
+        /// Change the account receiving the balance on destruct.
+        SetBeneficiary(beneficiary: Address) = 2,
+        /// Change the timelock delay itself.
+        SetDelay(delay: ChainEpoch) = 3,
+        /// Change the global and per-initiator withdrawal limits, `None` lifting
+        /// the limit.
+        SetRateLimits(global: Option<RateLimit>, caller: Option<RateLimit>) = 4,
+        /// Change the account allowed to pause the actor.
+        SetGuardian(guardian: ActorID) = 5,
+        /// Allow or disallow `transfer` to actors with this code.
+        SetTransferCode(code: Cid, allowed: bool) = 6,
+    }
+}
+
+/// An action waiting for its delay to pass.
+#[derive(Serialize_tuple, Deserialize_tuple, Clone, Debug, PartialEq)]
+pub struct QueuedAction {
+    pub action: TimelockAction,
+    /// Earliest epoch at which the action may be executed.
+    pub eta: ChainEpoch,
+}
+
+#[derive(Debug, Serialize_tuple, Deserialize_tuple)]
+pub struct QueueParams {
+    pub action: TimelockAction,
+}
+
+#[derive(Debug, Serialize_tuple, Deserialize_tuple)]
+pub struct QueueReturn {
+    pub id: u64,
+    pub eta: ChainEpoch,
+}
+
+#[derive(Debug, Serialize_tuple, Deserialize_tuple)]
+pub struct QueuedIDParams {
+    pub id: u64,
+}
+
+/// Queues `action` to run once the current delay has passed.
+pub fn enqueue(action: TimelockAction) -> QueueReturn {
+    match &action {
+        TimelockAction::SetDelay(delay) => {
+            if !(0..=MAX_TIMELOCK_DELAY).contains(delay) {
+                abort!(
+                    USR_ILLEGAL_ARGUMENT,
+                    "timelock delay {} must be between 0 and {}",
+                    delay,
+                    MAX_TIMELOCK_DELAY
+                );
+            }
+        }
+        TimelockAction::SetRateLimits(global, caller) => {
+            global
+                .iter()
+                .chain(caller.iter())
+                .for_each(RateLimit::validate);
+        }
+        TimelockAction::SetMinerMethod(method, true) => miner::validate_unrestricted(*method),
+        _ => {}
+    }
+
+    let mut state = State::load();
+    let id = state.next_queued_id;
+    state.next_queued_id += 1;
+    let eta = rt().curr_epoch() + state.timelock_delay;
+
+    state.queued = match map::set(
+        Blockstore,
+        &state.queued,
+        map::u64_key(id),
+        QueuedAction { action, eta },
+    ) {
+        Ok(queued) => queued,
+        Err(err) => abort!(USR_ILLEGAL_STATE, "{}", err),
+    };
+    state.record_event(EventPayload::ActionQueued(id, eta));
+    state.save();
+    QueueReturn { id, eta }
+}
+
+fn queued_action(state: &State, id: u64) -> QueuedAction {
+    match map::get(Blockstore, &state.queued, &map::u64_key(id)) {
+        Ok(Some(queued)) => queued,
+        Ok(None) => abort!(USR_NOT_FOUND, "queued action {} not found", id),
+        Err(err) => abort!(USR_ILLEGAL_STATE, "{}", err),
+    }
+}
+
+/// Method num 27.
+/// Queues an admin action. Returns a `QueueReturn`. Worker changes and
+/// changes to the `miner_call` allow-list are only queued once the signers
+/// approved them, through `multisig::propose`.
+pub fn queue(params: QueueParams) -> QueueReturn {
+    State::load().validate_admin();
+    match params.action {
+        TimelockAction::ChangeWorker(..) => abort!(
+            USR_FORBIDDEN,
+            "worker changes must be proposed to the signers"
+        ),
+        TimelockAction::SetMinerMethod(..) => abort!(
+            USR_FORBIDDEN,
+            "miner method changes must be proposed to the signers"
+        ),
+        _ => {}
+    }
+    enqueue(params.action)
+}
+
+/// Method num 28.
+/// Cancels a queued action before it runs.
+pub fn cancel_queued(params: QueuedIDParams) {
+    let mut state = State::load();
+    state.validate_admin();
+    queued_action(&state, params.id);
+    state.queued =
+        match map::remove::<QueuedAction>(Blockstore, &state.queued, &map::u64_key(params.id)) {
+            Ok(queued) => queued,
+            Err(err) => abort!(USR_ILLEGAL_STATE, "{}", err),
+        };
+    state.record_event(EventPayload::ActionCancelled(params.id));
+    state.save();
+}
+
+/// Method num 29.
+/// Executes a queued action whose delay has passed. Anyone may call this.
+pub fn execute_queued(params: QueuedIDParams) -> RawBytes {
+    let mut state = State::load();
+    let queued = queued_action(&state, params.id);
+    let epoch = rt().curr_epoch();
+    if epoch < queued.eta {
+        abort!(
+            USR_FORBIDDEN,
+            "queued action {} can't run before epoch {}, now {}",
+            params.id,
+            queued.eta,
+            epoch
+        );
+    }
+
+    // Drop the action before running it, so it can't run twice.
+    state.queued =
+        match map::remove::<QueuedAction>(Blockstore, &state.queued, &map::u64_key(params.id)) {
+            Ok(queued) => queued,
+            Err(err) => abort!(USR_ILLEGAL_STATE, "{}", err),
+        };
+    state.record_event(EventPayload::ActionExecuted(params.id));
+    state.save();
+
+    execute(queued.action)
+}
+
+/// Method num 30.
+/// Returns queued action `id`.
+pub fn get_queued(params: QueuedIDParams) -> QueuedAction {
+    let state = State::load();
+    queued_action(&state, params.id)
+}
+
+/// Runs an action whose delay has passed and returns its return value.
+fn execute(action: TimelockAction) -> RawBytes {
+    match action {
+        TimelockAction::ChangeWorker(miner, worker) => crate::exec_change_worker(miner, worker),
+        TimelockAction::SetMinerMethod(method, allowed) => {
+            let mut state = State::load();
+            state.miner_methods.retain(|m| *m != method);
+            if allowed {
+                state.miner_methods.push(method);
+            }
+            state.record_event(EventPayload::MinerMethodAllowed(method, allowed));
+            state.save();
+            RawBytes::default()
+        }
+        TimelockAction::SetBeneficiary(beneficiary) => {
+            let mut state = State::load();
+            state.beneficiary = Some(beneficiary);
+            state.record_event(EventPayload::BeneficiaryChanged(beneficiary));
+            state.save();
+            RawBytes::default()
+        }
+        TimelockAction::SetDelay(delay) => {
+            let mut state = State::load();
+            state.timelock_delay = delay;
+            state.record_event(EventPayload::DelayChanged(delay));
+            state.save();
+            RawBytes::default()
+        }
+        TimelockAction::SetRateLimits(global, caller) => {
+            let mut state = State::load();
+            state.global_limit = global;
+            state.caller_limit = caller;
+            state.record_event(EventPayload::RateLimitsChanged);
+            state.save();
+            RawBytes::default()
+        }
+        TimelockAction::SetGuardian(guardian) => {
+            let mut state = State::load();
+            state.guardian = guardian;
+            state.record_event(EventPayload::GuardianChanged(guardian));
+            state.save();
+            RawBytes::default()
+        }
+        TimelockAction::SetTransferCode(code, allowed) => {
+            let mut state = State::load();
+            state.transfer_codes.retain(|c| *c != code);
+            if allowed {
+                state.transfer_codes.push(code);
+            }
+            state.record_event(EventPayload::TransferCodeAllowed(code, allowed));
+            state.save();
+            RawBytes::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::runtime::MockRuntime;
+    use fvm_ipld_encoding::RawBytes;
+
+    #[test]
+    fn worker_changes_are_only_queued_once_approved() {
+        use super::{QueueParams, TimelockAction};
+        use crate::multisig::{Operation, ProposeParams, TxnIDParams};
+        use fvm_shared::address::Address;
+        use fvm_shared::error::ExitCode;
+
+        let rt = MockRuntime::construct(10);
+        let mut state = crate::State::load();
+        state.signers.push(11);
+        state.threshold = 2;
+        state.save();
+        let (miner, worker) = (Address::new_id(1000), Address::new_id(1001));
+
+        // The admin alone can't queue a worker change directly.
+        let params = RawBytes::serialize(QueueParams {
+            action: TimelockAction::ChangeWorker(miner, worker),
+        })
+        .unwrap();
+        let abort = rt.call(27, crate::dispatch, params).unwrap_err();
+        assert_eq!(abort.exit_code, ExitCode::USR_FORBIDDEN);
+        assert_eq!(crate::State::load().next_queued_id, 0);
+
+        // Proposed, it is queued once the second signer approves.
+        let params = RawBytes::serialize(ProposeParams {
+            op: Operation::ChangeWorker(miner, worker),
+        })
+        .unwrap();
+        rt.call(23, crate::dispatch, params).unwrap();
+        assert_eq!(crate::State::load().next_queued_id, 0);
+        rt.set_caller(11);
+        let params = RawBytes::serialize(TxnIDParams { tx_id: 0 }).unwrap();
+        rt.call(24, crate::dispatch, params).unwrap();
+        assert_eq!(crate::State::load().next_queued_id, 1);
+    }
+}
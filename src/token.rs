@@ -0,0 +1,576 @@
+//! Depositor shares of the pool, exposed as an FRC-46 fungible token. Shares
+//! are minted by `deposit` in proportion to the pool balance, and can be
+//! transferred, delegated to operators and burnt through the standard FRC-46
+//! methods, which are dispatched by their FRC-42 method numbers.
+//!
+//! Shares can't be redeemed for funds. Burning shares leaves the pool balance
+//! unchanged, so their value goes to the remaining holders.
+
+use frc42_dispatch::method_hash;
+use fvm_ipld_encoding::tuple::{Deserialize_tuple, Serialize_tuple};
+use fvm_ipld_encoding::RawBytes;
+use fvm_shared::address::Address;
+use fvm_shared::econ::TokenAmount;
+use fvm_shared::{ActorID, MethodNum};
+
+use crate::blockstore::Blockstore;
+use crate::datacap::{
+    FRC46TokenReceived, UniversalReceiverParams, FRC46_TOKEN_TYPE, RECEIVER_HOOK,
+};
+use crate::events::EventPayload;
+use crate::{guard, map, runtime::rt, State};
+
+pub const TOKEN_NAME: &str = "FVM Actor Example Pool Share";
+pub const TOKEN_SYMBOL: &str = "FAPS";
+
+/// FRC-42 method numbers of the FRC-46 token methods.
+pub const NAME: MethodNum = method_hash!("Name");
+pub const SYMBOL: MethodNum = method_hash!("Symbol");
+pub const TOTAL_SUPPLY: MethodNum = method_hash!("TotalSupply");
+pub const BALANCE_OF: MethodNum = method_hash!("BalanceOf");
+pub const TRANSFER: MethodNum = method_hash!("Transfer");
+pub const TRANSFER_FROM: MethodNum = method_hash!("TransferFrom");
+pub const INCREASE_ALLOWANCE: MethodNum = method_hash!("IncreaseAllowance");
+pub const DECREASE_ALLOWANCE: MethodNum = method_hash!("DecreaseAllowance");
+pub const REVOKE_ALLOWANCE: MethodNum = method_hash!("RevokeAllowance");
+pub const BURN: MethodNum = method_hash!("Burn");
+
+#[derive(Debug, Serialize_tuple, Deserialize_tuple)]
+pub struct TransferParams {
+    pub to: Address,
+    pub amount: TokenAmount,
+    pub operator_data: RawBytes,
+}
+
+#[derive(Debug, Serialize_tuple, Deserialize_tuple)]
+pub struct TransferReturn {
+    pub from_balance: TokenAmount,
+    pub to_balance: TokenAmount,
+    pub recipient_data: RawBytes,
+}
+
+#[derive(Debug, Serialize_tuple, Deserialize_tuple)]
+pub struct TransferFromParams {
+    pub from: Address,
+    pub to: Address,
+    pub amount: TokenAmount,
+    pub operator_data: RawBytes,
+}
+
+#[derive(Debug, Serialize_tuple, Deserialize_tuple)]
+pub struct TransferFromReturn {
+    pub from_balance: TokenAmount,
+    pub to_balance: TokenAmount,
+    pub allowance: TokenAmount,
+    pub recipient_data: RawBytes,
+}
+
+#[derive(Debug, Serialize_tuple, Deserialize_tuple)]
+pub struct IncreaseAllowanceParams {
+    pub operator: Address,
+    pub increase: TokenAmount,
+}
+
+#[derive(Debug, Serialize_tuple, Deserialize_tuple)]
+pub struct DecreaseAllowanceParams {
+    pub operator: Address,
+    pub decrease: TokenAmount,
+}
+
+#[derive(Debug, Serialize_tuple, Deserialize_tuple)]
+pub struct RevokeAllowanceParams {
+    pub operator: Address,
+}
+
+#[derive(Debug, Serialize_tuple, Deserialize_tuple)]
+pub struct BurnParams {
+    pub amount: TokenAmount,
+}
+
+#[derive(Debug, Serialize_tuple, Deserialize_tuple)]
+pub struct BurnReturn {
+    pub balance: TokenAmount,
+}
+
+/// Shares counted on top of the supply when pricing a deposit.
+pub const VIRTUAL_SHARES: u64 = 1000;
+/// Atto counted on top of the pool value when pricing a deposit.
+pub const VIRTUAL_ASSETS: u64 = 1;
+
+/// Shares minted for depositing `amount` into a pool worth `pool_value`
+/// before the deposit, with `supply` shares outstanding, rounding down. The
+/// pool is priced as if it held `VIRTUAL_ASSETS` more atto backing
+/// `VIRTUAL_SHARES` more shares, so the first deposit mints 1000 shares per
+/// atto, and funds sent to the pool without a deposit can't inflate the share
+/// price enough to round a later deposit down to nothing.
+pub fn shares_for_deposit(
+    amount: &TokenAmount,
+    supply: &TokenAmount,
+    pool_value: &TokenAmount,
+) -> TokenAmount {
+    let shares = supply.atto() + VIRTUAL_SHARES;
+    let assets = pool_value.atto() + VIRTUAL_ASSETS;
+    TokenAmount::from_atto(amount.atto() * shares / assets)
+}
+
+impl State {
+    /// Shares held by `owner`.
+    pub fn share_balance(&self, owner: ActorID) -> TokenAmount {
+        match map::get(Blockstore, &self.share_balances, &map::u64_key(owner)) {
+            Ok(balance) => balance.unwrap_or_else(|| TokenAmount::from_atto(0)),
+            Err(err) => abort!(USR_ILLEGAL_STATE, "{}", err),
+        }
+    }
+
+    pub(crate) fn set_share_balance(&mut self, owner: ActorID, balance: TokenAmount) {
+        let key = map::u64_key(owner);
+        let balances = if balance == TokenAmount::from_atto(0) {
+            map::remove::<TokenAmount>(Blockstore, &self.share_balances, &key)
+        } else {
+            map::set(Blockstore, &self.share_balances, key, balance)
+        };
+        self.share_balances = match balances {
+            Ok(balances) => balances,
+            Err(err) => abort!(USR_ILLEGAL_STATE, "{}", err),
+        };
+    }
+
+    /// Shares `operator` may still transfer on behalf of `owner`.
+    pub fn share_allowance(&self, owner: ActorID, operator: ActorID) -> TokenAmount {
+        match map::get(
+            Blockstore,
+            &self.share_allowances,
+            &map::pair_key(owner, operator),
+        ) {
+            Ok(allowance) => allowance.unwrap_or_else(|| TokenAmount::from_atto(0)),
+            Err(err) => abort!(USR_ILLEGAL_STATE, "{}", err),
+        }
+    }
+
+    fn set_share_allowance(&mut self, owner: ActorID, operator: ActorID, allowance: TokenAmount) {
+        let key = map::pair_key(owner, operator);
+        let allowances = if allowance == TokenAmount::from_atto(0) {
+            map::remove::<TokenAmount>(Blockstore, &self.share_allowances, &key)
+        } else {
+            map::set(Blockstore, &self.share_allowances, key, allowance)
+        };
+        self.share_allowances = match allowances {
+            Ok(allowances) => allowances,
+            Err(err) => abort!(USR_ILLEGAL_STATE, "{}", err),
+        };
+    }
+
+    /// Moves `amount` shares from `from` to `to`, returning both new balances.
+    pub(crate) fn move_shares(
+        &mut self,
+        from: ActorID,
+        to: ActorID,
+        amount: &TokenAmount,
+    ) -> (TokenAmount, TokenAmount) {
+        validate_amount(amount);
+        let from_balance = self.share_balance(from);
+        if from_balance < *amount {
+            abort!(
+                USR_INSUFFICIENT_FUNDS,
+                "f0{} holds {} shares, can't transfer {}",
+                from,
+                from_balance,
+                amount
+            );
+        }
+        self.set_share_balance(from, from_balance - amount.clone());
+        let to_balance = self.share_balance(to) + amount.clone();
+        self.set_share_balance(to, to_balance);
+        (self.share_balance(from), self.share_balance(to))
+    }
+}
+
+fn validate_amount(amount: &TokenAmount) {
+    if *amount < TokenAmount::from_atto(0) {
+        abort!(USR_ILLEGAL_ARGUMENT, "negative amount {}", amount);
+    }
+}
+
+/// Calls the FRC-46 receiver hook of `to` for shares it received. The
+/// balances in `state` must already be updated; it is saved along with the
+/// call. Returns the data the receiver returned.
+fn call_receiver_hook(
+    state: &mut State,
+    operator: ActorID,
+    from: ActorID,
+    to: ActorID,
+    amount: TokenAmount,
+    operator_data: RawBytes,
+) -> RawBytes {
+    let received = FRC46TokenReceived {
+        from,
+        to,
+        operator,
+        amount,
+        operator_data,
+        token_data: RawBytes::default(),
+    };
+    let params = UniversalReceiverParams {
+        type_: FRC46_TOKEN_TYPE,
+        payload: crate::serialize_params(&received),
+    };
+    match guard::send_locked(
+        state,
+        &Address::new_id(to),
+        RECEIVER_HOOK,
+        crate::serialize_params(&params),
+        TokenAmount::from_atto(0),
+    ) {
+        Ok(receipt) => receipt.return_data,
+        Err(err) => err.abort(),
+    }
+}
+
+fn resolve_id(addr: &Address) -> ActorID {
+    match rt().resolve_address(addr) {
+        Some(id) => id,
+        None => abort!(USR_NOT_FOUND, "actor {} not found", addr),
+    }
+}
+
+/// Method num 49.
+/// Deposits the value sent into the pool, minting shares to the caller.
+/// Deposits too small to mint a share are refused. Returns the shares minted.
+pub fn deposit() -> TokenAmount {
+    let amount = rt().value_received();
+    let depositor = rt().caller();
+
+    let mut state = State::load();
+    // The deposit is already part of the balance.
+    let pool_value = rt().current_balance() - amount.clone();
+    let shares = shares_for_deposit(&amount, &state.share_supply, &pool_value);
+    if shares <= TokenAmount::from_atto(0) {
+        abort!(
+            USR_ILLEGAL_ARGUMENT,
+            "deposit of {} mints no shares",
+            amount
+        );
+    }
+
+    state.share_supply = state.share_supply.clone() + shares.clone();
+    let balance = state.share_balance(depositor) + shares.clone();
+    state.set_share_balance(depositor, balance);
+    state.record_event(EventPayload::SharesMinted(
+        depositor,
+        amount,
+        shares.clone(),
+    ));
+    state.save();
+    shares
+}
+
+/// FRC-46 `Name`.
+pub fn name() -> String {
+    TOKEN_NAME.to_string()
+}
+
+/// FRC-46 `Symbol`.
+pub fn symbol() -> String {
+    TOKEN_SYMBOL.to_string()
+}
+
+/// FRC-46 `TotalSupply`.
+pub fn total_supply() -> TokenAmount {
+    State::load().share_supply
+}
+
+/// FRC-46 `BalanceOf`. Unknown addresses hold no shares.
+pub fn balance_of(owner: Address) -> TokenAmount {
+    match rt().resolve_address(&owner) {
+        Some(id) => State::load().share_balance(id),
+        None => TokenAmount::from_atto(0),
+    }
+}
+
+/// FRC-46 `Transfer`. Moves shares of the caller, then calls the receiver
+/// hook of the recipient.
+pub fn transfer(params: TransferParams) -> TransferReturn {
+    let from = rt().caller();
+    let to = resolve_id(&params.to);
+    let mut state = State::load();
+    let (from_balance, to_balance) = state.move_shares(from, to, &params.amount);
+    state.record_event(EventPayload::SharesTransferred(
+        from,
+        to,
+        params.amount.clone(),
+    ));
+
+    let recipient_data = call_receiver_hook(
+        &mut state,
+        from,
+        from,
+        to,
+        params.amount,
+        params.operator_data,
+    );
+    state.save();
+
+    TransferReturn {
+        from_balance,
+        to_balance,
+        recipient_data,
+    }
+}
+
+/// FRC-46 `TransferFrom`. Moves shares on behalf of their owner, spending the
+/// caller's allowance, then calls the receiver hook of the recipient.
+pub fn transfer_from(params: TransferFromParams) -> TransferFromReturn {
+    let operator = rt().caller();
+    let from = resolve_id(&params.from);
+    let to = resolve_id(&params.to);
+    let mut state = State::load();
+
+    let allowance = state.share_allowance(from, operator);
+    if allowance < params.amount {
+        abort!(
+            USR_INSUFFICIENT_FUNDS,
+            "f0{} may transfer {} shares of f0{}, not {}",
+            operator,
+            allowance,
+            from,
+            params.amount
+        );
+    }
+    let allowance = allowance - params.amount.clone();
+    state.set_share_allowance(from, operator, allowance.clone());
+    let (from_balance, to_balance) = state.move_shares(from, to, &params.amount);
+    state.record_event(EventPayload::SharesTransferred(
+        from,
+        to,
+        params.amount.clone(),
+    ));
+
+    let recipient_data = call_receiver_hook(
+        &mut state,
+        operator,
+        from,
+        to,
+        params.amount,
+        params.operator_data,
+    );
+    state.save();
+
+    TransferFromReturn {
+        from_balance,
+        to_balance,
+        allowance,
+        recipient_data,
+    }
+}
+
+/// FRC-46 `IncreaseAllowance`. Returns the new allowance.
+pub fn increase_allowance(params: IncreaseAllowanceParams) -> TokenAmount {
+    validate_amount(&params.increase);
+
+    let owner = rt().caller();
+    let operator = resolve_id(&params.operator);
+    let mut state = State::load();
+    let allowance = state.share_allowance(owner, operator) + params.increase;
+    state.set_share_allowance(owner, operator, allowance.clone());
+    state.save();
+    allowance
+}
+
+/// FRC-46 `DecreaseAllowance`. The allowance doesn't go below zero. Returns
+/// the new allowance.
+pub fn decrease_allowance(params: DecreaseAllowanceParams) -> TokenAmount {
+    validate_amount(&params.decrease);
+
+    let owner = rt().caller();
+    let operator = resolve_id(&params.operator);
+    let mut state = State::load();
+    let allowance = state.share_allowance(owner, operator);
+    let allowance = if allowance > params.decrease {
+        allowance - params.decrease
+    } else {
+        TokenAmount::from_atto(0)
+    };
+    state.set_share_allowance(owner, operator, allowance.clone());
+    state.save();
+    allowance
+}
+
+/// FRC-46 `RevokeAllowance`. Sets the allowance to zero. Returns the allowance
+/// before.
+pub fn revoke_allowance(params: RevokeAllowanceParams) -> TokenAmount {
+    let owner = rt().caller();
+    let operator = resolve_id(&params.operator);
+    let mut state = State::load();
+    let allowance = state.share_allowance(owner, operator);
+    state.set_share_allowance(owner, operator, TokenAmount::from_atto(0));
+    state.save();
+    allowance
+}
+
+/// FRC-46 `Burn`. Destroys shares of the caller. Nothing is paid out; the
+/// value of the shares goes to the remaining holders.
+pub fn burn(params: BurnParams) -> BurnReturn {
+    validate_amount(&params.amount);
+
+    let owner = rt().caller();
+    let mut state = State::load();
+    let balance = state.share_balance(owner);
+    if balance < params.amount {
+        abort!(
+            USR_INSUFFICIENT_FUNDS,
+            "f0{} holds {} shares, can't burn {}",
+            owner,
+            balance,
+            params.amount
+        );
+    }
+    let balance = balance - params.amount.clone();
+    state.set_share_balance(owner, balance.clone());
+    state.share_supply = state.share_supply.clone() - params.amount.clone();
+    state.record_event(EventPayload::SharesBurnt(owner, params.amount));
+    state.save();
+    BurnReturn { balance }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::runtime::MockRuntime;
+    use fvm_ipld_encoding::RawBytes;
+
+    #[test]
+    fn deposits_mint_shares_pro_rata() {
+        use fvm_shared::econ::TokenAmount;
+
+        let atto = |atto: u64| TokenAmount::from_atto(atto);
+        // The first deposit mints `VIRTUAL_SHARES` shares per atto.
+        assert_eq!(
+            super::shares_for_deposit(&atto(100), &atto(0), &atto(0)),
+            atto(100_000)
+        );
+        // Later ones mint in proportion to the pool value, rounding down.
+        assert_eq!(
+            super::shares_for_deposit(&atto(50), &atto(100_000), &atto(100)),
+            atto(50_000)
+        );
+        assert_eq!(
+            super::shares_for_deposit(&atto(1), &atto(100_000), &atto(300)),
+            atto(335)
+        );
+        // A holder of the only share sending funds to the pool can't make a
+        // later deposit mint nothing.
+        assert_eq!(
+            super::shares_for_deposit(&atto(1000), &atto(1), &atto(1_000_000)),
+            atto(1)
+        );
+    }
+
+    #[test]
+    fn deposits_that_mint_no_shares_are_refused() {
+        use fvm_shared::econ::TokenAmount;
+        use fvm_shared::error::ExitCode;
+
+        let rt = MockRuntime::construct(10);
+        let deposit = |amount: u64, balance: u64| {
+            rt.value_received.replace(TokenAmount::from_atto(amount));
+            rt.set_balance(TokenAmount::from_atto(balance));
+            rt.call(49, crate::dispatch, RawBytes::default())
+        };
+
+        let abort = deposit(0, 0).unwrap_err();
+        assert_eq!(abort.exit_code, ExitCode::USR_ILLEGAL_ARGUMENT);
+
+        let ret = deposit(100, 100).unwrap().unwrap();
+        assert_eq!(
+            ret.deserialize::<TokenAmount>().unwrap(),
+            TokenAmount::from_atto(100_000)
+        );
+        assert_eq!(
+            crate::State::load().share_balance(10),
+            TokenAmount::from_atto(100_000)
+        );
+
+        // Funds sent without a deposit raise the share price, until one atto
+        // buys less than a share.
+        let abort = deposit(1, 1_000_000_000).unwrap_err();
+        assert_eq!(abort.exit_code, ExitCode::USR_ILLEGAL_ARGUMENT);
+        assert_eq!(
+            crate::State::load().share_supply,
+            TokenAmount::from_atto(100_000)
+        );
+    }
+
+    #[test]
+    fn share_allowances_can_be_decreased_and_revoked() {
+        use super::{
+            DecreaseAllowanceParams, IncreaseAllowanceParams, RevokeAllowanceParams,
+            DECREASE_ALLOWANCE, INCREASE_ALLOWANCE, REVOKE_ALLOWANCE,
+        };
+        use fvm_shared::address::Address;
+        use fvm_shared::econ::TokenAmount;
+
+        let rt = MockRuntime::construct(10);
+        let operator = Address::new_id(11);
+        let allowance = |ret: Option<RawBytes>| ret.unwrap().deserialize::<TokenAmount>().unwrap();
+
+        let params = RawBytes::serialize(IncreaseAllowanceParams {
+            operator,
+            increase: TokenAmount::from_atto(100),
+        })
+        .unwrap();
+        let ret = rt
+            .call(INCREASE_ALLOWANCE, crate::dispatch, params)
+            .unwrap();
+        assert_eq!(allowance(ret), TokenAmount::from_atto(100));
+
+        let decrease = |amount: u64| {
+            RawBytes::serialize(DecreaseAllowanceParams {
+                operator,
+                decrease: TokenAmount::from_atto(amount),
+            })
+            .unwrap()
+        };
+        let ret = rt
+            .call(DECREASE_ALLOWANCE, crate::dispatch, decrease(30))
+            .unwrap();
+        assert_eq!(allowance(ret), TokenAmount::from_atto(70));
+        // Decreasing by more than the allowance leaves nothing.
+        let ret = rt
+            .call(DECREASE_ALLOWANCE, crate::dispatch, decrease(100))
+            .unwrap();
+        assert_eq!(allowance(ret), TokenAmount::from_atto(0));
+        assert_eq!(
+            crate::State::load().share_allowance(10, 11),
+            TokenAmount::from_atto(0)
+        );
+
+        let params = RawBytes::serialize(IncreaseAllowanceParams {
+            operator,
+            increase: TokenAmount::from_atto(50),
+        })
+        .unwrap();
+        rt.call(INCREASE_ALLOWANCE, crate::dispatch, params)
+            .unwrap();
+        let params = RawBytes::serialize(RevokeAllowanceParams { operator }).unwrap();
+        let ret = rt.call(REVOKE_ALLOWANCE, crate::dispatch, params).unwrap();
+        assert_eq!(allowance(ret), TokenAmount::from_atto(50));
+        assert_eq!(
+            crate::State::load().share_allowance(10, 11),
+            TokenAmount::from_atto(0)
+        );
+    }
+
+    #[test]
+    fn share_ledger_moves_balances() {
+        use fvm_shared::econ::TokenAmount;
+
+        // Native builds keep the actor's blocks in memory.
+        let mut state = crate::State::new(1);
+        let atto = |atto: u64| TokenAmount::from_atto(atto);
+        state.set_share_balance(10, atto(100));
+
+        assert_eq!(state.move_shares(10, 11, &atto(30)), (atto(70), atto(30)));
+        assert_eq!(state.move_shares(10, 11, &atto(70)), (atto(0), atto(100)));
+        assert_eq!(state.share_balance(10), atto(0));
+        assert_eq!(state.share_balance(12), atto(0));
+    }
+}
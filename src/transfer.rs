@@ -0,0 +1,253 @@
+//! Admin-initiated transfers out of the actor balance. `transfer` reaches any
+//! actor whose code is allow-listed, and needs the signers' approval above
+//! `State::transfer_limit`; the funding helpers only reach the power actor and
+//! managed miners, so they need neither.
+
+use cid::Cid;
+use fvm_ipld_encoding::tuple::{Deserialize_tuple, Serialize_tuple};
+use fvm_ipld_encoding::RawBytes;
+use fvm_shared::address::Address;
+use fvm_shared::econ::TokenAmount;
+use fvm_shared::receipt::Receipt;
+use fvm_shared::{ActorID, MethodNum, METHOD_SEND};
+
+use crate::events::EventPayload;
+use crate::guard;
+use crate::runtime::rt;
+use crate::State;
+
+#[derive(Debug, Serialize_tuple, Deserialize_tuple)]
+pub struct TransferParams {
+    pub to: Address,
+    pub amount: TokenAmount,
+    pub method: MethodNum,
+    pub params: RawBytes,
+}
+
+#[derive(Debug, Serialize_tuple, Deserialize_tuple)]
+pub struct FundMinerParams {
+    pub miner: Address,
+    pub amount: TokenAmount,
+}
+
+/// Sends `amount` and `params` to `to` on behalf of the admin, counting
+/// `amount` against the admin's rate limits, and records `event` before
+/// sending.
+pub(crate) fn send_value(
+    to: &Address,
+    method: MethodNum,
+    params: RawBytes,
+    amount: TokenAmount,
+    event: EventPayload,
+) -> Receipt {
+    let state = State::load();
+    state.validate_admin();
+    let admin = state.admin;
+    send_from(state, admin, to, method, params, amount, event)
+}
+
+/// Like `send_value`, for a send `initiator` started and the caller was
+/// already checked for.
+fn send_from(
+    mut state: State,
+    initiator: ActorID,
+    to: &Address,
+    method: MethodNum,
+    params: RawBytes,
+    amount: TokenAmount,
+    event: EventPayload,
+) -> Receipt {
+    state.validate_not_paused();
+    state.charge_rate_limits(initiator, &amount);
+    state.record_event(event);
+
+    let receipt = match guard::send_locked(&mut state, to, method, params, amount) {
+        Ok(receipt) => receipt,
+        Err(err) => err.abort(),
+    };
+    state.save();
+    receipt
+}
+
+/// Resolves `miner` and aborts unless it is managed by this actor.
+pub(crate) fn managed_miner(miner: &Address) -> Address {
+    let miner = crate::resolve_id_address(miner);
+    if !State::load().is_managed_miner(&miner) {
+        abort!(
+            USR_FORBIDDEN,
+            "miner {} is not managed by this actor",
+            miner
+        );
+    }
+    miner
+}
+
+/// Aborts unless the code of `to` is allow-listed for `transfer`.
+pub(crate) fn validate_transfer_code(to: &Address) {
+    let code: Cid = match rt().get_actor_code_cid(to) {
+        Some(code) => code,
+        None => abort!(USR_NOT_FOUND, "actor {} not found", to),
+    };
+    if !State::load().transfer_codes.contains(&code) {
+        abort!(
+            USR_FORBIDDEN,
+            "transfers to actors with code {} are not allowed",
+            code
+        );
+    }
+}
+
+/// Method num 33.
+/// Sends funds, and optionally a method call, to an actor whose code is
+/// allow-listed. Only the admin may call this, for amounts up to
+/// `State::transfer_limit`; larger transfers are proposed to the signers as
+/// `Operation::Transfer`. Returns the callee's receipt.
+pub fn transfer(params: TransferParams) -> Receipt {
+    let limit = State::load().transfer_limit;
+    if params.amount > limit {
+        abort!(
+            USR_FORBIDDEN,
+            "transfers of more than {} must be proposed to the signers",
+            limit
+        );
+    }
+    validate_transfer_code(&params.to);
+
+    let event = EventPayload::Transferred(params.to, params.method, params.amount.clone());
+    send_value(
+        &params.to,
+        params.method,
+        params.params,
+        params.amount,
+        event,
+    )
+}
+
+/// Sends a transfer once the signers approved it. The amount counts against
+/// the rate limits of `initiator`. Returns the callee's receipt.
+pub(crate) fn exec_transfer(
+    initiator: ActorID,
+    to: Address,
+    amount: TokenAmount,
+    method: MethodNum,
+    params: RawBytes,
+) -> RawBytes {
+    validate_transfer_code(&to);
+
+    let event = EventPayload::Transferred(to, method, amount.clone());
+    let receipt = send_from(State::load(), initiator, &to, method, params, amount, event);
+    match RawBytes::serialize(&receipt) {
+        Ok(ret) => ret,
+        Err(err) => abort!(
+            USR_ILLEGAL_STATE,
+            "failed to serialize return value: {:?}",
+            err
+        ),
+    }
+}
+
+/// Method num 34.
+/// Adds funds to the available balance of a managed miner. Only the admin may
+/// call this.
+pub fn fund_miner(params: FundMinerParams) {
+    fund_managed_miner(
+        &params.miner,
+        METHOD_SEND,
+        params.amount,
+        EventPayload::MinerFunded,
+    );
+}
+
+/// Sends `amount` to the managed miner `miner` with `method`, through
+/// `send_value`. `event` is recorded with the miner's ID address and `amount`.
+pub(crate) fn fund_managed_miner(
+    miner: &Address,
+    method: MethodNum,
+    amount: TokenAmount,
+    event: fn(Address, TokenAmount) -> EventPayload,
+) {
+    let miner = managed_miner(miner);
+    let event = event(miner, amount.clone());
+    send_value(&miner, method, RawBytes::default(), amount, event);
+}
+
+#[cfg(test)]
+mod test {
+    use crate::runtime::{ok_receipt, MockRuntime};
+    use cid::Cid;
+    use fvm_ipld_encoding::RawBytes;
+
+    #[test]
+    fn transfers_above_the_limit_are_proposed() {
+        use super::TransferParams;
+        use crate::multisig::{Operation, ProposeParams, ProposeReturn};
+        use fvm_shared::address::Address;
+        use fvm_shared::econ::TokenAmount;
+        use fvm_shared::error::ExitCode;
+        use fvm_shared::receipt::Receipt;
+
+        let rt = MockRuntime::construct(10);
+        rt.set_balance(TokenAmount::from_atto(1000));
+        let to = Address::new_id(1000);
+        let code = Cid::try_from("bafy2bzacea6bvgucghtd66eubqazpknwqqpfywtdgp5qxludjsa6tyd6cxwuy")
+            .unwrap();
+        rt.add_actor(to, 1000, code);
+        let mut state = crate::State::load();
+        state.transfer_codes.push(code);
+        state.save();
+        let transfer = |amount: u64| {
+            RawBytes::serialize(TransferParams {
+                to,
+                amount: TokenAmount::from_atto(amount),
+                method: fvm_shared::METHOD_SEND,
+                params: RawBytes::default(),
+            })
+            .unwrap()
+        };
+
+        // Nothing may be transferred alone until the signers set a limit.
+        let abort = rt.call(33, crate::dispatch, transfer(50)).unwrap_err();
+        assert_eq!(abort.exit_code, ExitCode::USR_FORBIDDEN);
+        let params = RawBytes::serialize(ProposeParams {
+            op: Operation::SetTransferLimit(TokenAmount::from_atto(50)),
+        })
+        .unwrap();
+        rt.call(23, crate::dispatch, params).unwrap();
+
+        rt.expect_send(
+            to,
+            fvm_shared::METHOD_SEND,
+            RawBytes::default(),
+            TokenAmount::from_atto(50),
+            ok_receipt(RawBytes::default()),
+        );
+        rt.call(33, crate::dispatch, transfer(50)).unwrap();
+        rt.verify();
+        let abort = rt.call(33, crate::dispatch, transfer(51)).unwrap_err();
+        assert_eq!(abort.exit_code, ExitCode::USR_FORBIDDEN);
+
+        // Larger amounts are sent once approved, here by the only signer.
+        rt.expect_send(
+            to,
+            fvm_shared::METHOD_SEND,
+            RawBytes::default(),
+            TokenAmount::from_atto(100),
+            ok_receipt(RawBytes::default()),
+        );
+        let params = RawBytes::serialize(ProposeParams {
+            op: Operation::Transfer(
+                to,
+                TokenAmount::from_atto(100),
+                fvm_shared::METHOD_SEND,
+                RawBytes::default(),
+            ),
+        })
+        .unwrap();
+        let ret = rt.call(23, crate::dispatch, params).unwrap();
+        let ret: ProposeReturn = ret.unwrap().deserialize().unwrap();
+        let receipt: Receipt = ret.ret.deserialize().unwrap();
+        assert!(receipt.exit_code.is_success());
+        rt.verify();
+        assert_eq!(*rt.balance.borrow(), TokenAmount::from_atto(850));
+    }
+}